@@ -1,125 +1,66 @@
 // This file contains built-in abstractions.
 
+use super::host::Host;
 use super::interpreter::EvaluationValue;
 
 use anyhow::{Result, bail};
-use crossterm::event::{self, KeyCode};
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::{
-    io::{self, Write},
-    time::Duration,
-};
+use std::time::Duration;
 
 // Input's modes are separated, due to unnessary complexicity it creates.
-pub fn abstraction_input_char() -> Result<EvaluationValue> {
-    crossterm::terminal::enable_raw_mode().unwrap();
-    let result;
-    loop {
-        if event::poll(Duration::from_millis(10)).unwrap() {
-            if let event::Event::Key(key_event) = event::read().unwrap() {
-                match key_event.code {
-                    KeyCode::Char(c) => {
-                        result = c as u8;
-                        break;
-                    }
-                    KeyCode::Enter => {
-                        result = 10;
-                        break;
-                    }
-                    _ => {}
-                }
-            }
-        }
+// Both return HALT once the host has no more input.
+pub fn abstraction_input_char(host: &mut dyn Host) -> Result<EvaluationValue> {
+    match host.read_char()? {
+        Some(c) => Ok(EvaluationValue::Literal(c as u8 as f64)),
+        None => Ok(EvaluationValue::HALT),
     }
-    crossterm::terminal::disable_raw_mode().unwrap();
-    Ok(EvaluationValue::Literal(result as f64))
 }
 
-pub fn abstraction_input_numeric() -> Result<EvaluationValue> {
-    crossterm::terminal::enable_raw_mode().unwrap();
-    let mut is_dot_allowed = true;
-    let mut is_e_allowed = false;
-    let mut is_sign_allowed = false;
-    let mut result = String::new();
-    let mut print = false;
-    loop {
-        if event::poll(Duration::from_millis(10)).unwrap() {
-            if let event::Event::Key(key_event) = event::read().unwrap() {
-                match key_event.code {
-                    // is_e_allowed along isn't sufficient to determine if e can be added.
-                    KeyCode::Char('e') if is_e_allowed && !result.contains('e') => {
-                        result.push('e');
-                        is_dot_allowed = false;
-                        is_e_allowed = false;
-                        is_sign_allowed = true;
-                        print = true;
-                    }
-                    KeyCode::Char('+') if is_sign_allowed => {
-                        result.push('+');
-                        is_sign_allowed = false;
-                        print = true;
-                    }
-                    KeyCode::Char('-') if is_sign_allowed => {
-                        result.push('-');
-                        is_sign_allowed = false;
-                        print = true;
-                    }
-                    KeyCode::Char('.') if is_dot_allowed => {
-                        result.push('.');
-                        is_dot_allowed = false;
-                        print = true;
-                        // After, e if there is a digit, sign is no longer allowed;
-                        // i.e 10e10;
-                        is_sign_allowed = !is_e_allowed;
-                        is_e_allowed = true
-                    }
+// Unlike the character mode, nothing past ASCII is cut off.
+pub fn abstraction_input_code_point(host: &mut dyn Host) -> Result<EvaluationValue> {
+    match host.read_char()? {
+        Some(c) => Ok(EvaluationValue::Literal(c as u32 as f64)),
+        None => Ok(EvaluationValue::HALT),
+    }
+}
 
-                    KeyCode::Char(c) if c.is_ascii_digit() => {
-                        result.push(c);
-                        is_e_allowed = true;
-                        print = true;
-                    }
-                    KeyCode::Enter => {
-                        break;
-                    }
-                    _ => {}
-                }
-            }
-        }
-        if print {
-            write!(io::stdout(), "{}", result.chars().last().unwrap_or('\r'))?;
-            io::stdout().flush()?;
-            print = false;
-        }
+pub fn abstraction_input_numeric(host: &mut dyn Host) -> Result<EvaluationValue> {
+    match host.read_number()? {
+        Some(number) => Ok(EvaluationValue::Literal(number)),
+        None => Ok(EvaluationValue::HALT),
     }
-    crossterm::terminal::disable_raw_mode().unwrap();
-    let result_num = result.parse::<f64>()?;
-    Ok(EvaluationValue::Literal(result_num))
 }
 
 // Bob Dylan is quite good
 // listening to Knocking on Heaven's Door rn.
-pub fn abstraction_ascii(ascii: u8) -> Result<EvaluationValue> {
-    io::stdout().write_all(&[ascii]).unwrap();
-    io::stdout().flush()?;
+pub fn abstraction_ascii(host: &mut dyn Host, ascii: u8) -> Result<EvaluationValue> {
+    host.write_bytes(&[ascii])?;
     Ok(EvaluationValue::Literal(ascii as f64))
 }
 
-pub fn abstraction_print(numeric_value: f64) -> Result<EvaluationValue> {
-    write!(io::stdout(), "{}", numeric_value)?;
-    io::stdout().flush()?;
+// Writes the code point UTF-8 encoded, λ@ascii writes a single raw byte.
+pub fn abstraction_char(host: &mut dyn Host, code_point: u32) -> Result<EvaluationValue> {
+    let Some(c) = char::from_u32(code_point) else {
+        bail!("{} is not a Unicode scalar value", code_point);
+    };
+    host.write_bytes(c.encode_utf8(&mut [0; 4]).as_bytes())?;
+    Ok(EvaluationValue::Literal(code_point as f64))
+}
+
+pub fn abstraction_print(host: &mut dyn Host, numeric_value: f64) -> Result<EvaluationValue> {
+    host.write_number(numeric_value)?;
     Ok(EvaluationValue::Literal(numeric_value))
 }
 
-pub fn abstraction_time() -> Result<EvaluationValue> {
-    let current_time = SystemTime::now();
-    match current_time.duration_since(UNIX_EPOCH) {
-        Ok(stamp) => Ok(EvaluationValue::Literal(stamp.as_millis() as f64)),
-        Err(e) => bail!("SystemTimeError difference: {:?}", e.duration()),
-    }
+pub fn abstraction_write(host: &mut dyn Host, text: &str) -> Result<EvaluationValue> {
+    host.write_bytes(text.as_bytes())?;
+    Ok(EvaluationValue::String(text.into()))
+}
+
+pub fn abstraction_time(host: &mut dyn Host) -> Result<EvaluationValue> {
+    Ok(EvaluationValue::Literal(host.now()?))
 }
 
-pub fn abstraction_sleep(time: f64) -> Result<EvaluationValue> {
-    std::thread::sleep(Duration::from_millis(time as u64));
+pub fn abstraction_sleep(host: &mut dyn Host, time: f64) -> Result<EvaluationValue> {
+    host.sleep(Duration::from_millis(time as u64));
     Ok(EvaluationValue::Literal(time))
 }
@@ -1,9 +1,35 @@
-use super::lexer::TokenKind;
+use super::error::{Diagnostic, Span};
+use super::lexer::{Token, TokenKind};
 use super::throw_syntax_error;
 use crate::lexer::Operator;
 
-use anyhow::{Result, bail};
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, rc::Rc};
+
+type Result<T> = std::result::Result<T, Diagnostic>;
+
+const IF_HINT: &str = "a conditional is written if condition then a else b";
+
+const ABSTRACTION_HINT: &str =
+    "an abstraction is written λparam. body, or \\param. body, λx y. body takes two";
+
+const LIST_HINT: &str =
+    "a list is written [a, b, c], or [a, b, ..rest] to put a and b in front of rest";
+
+// For a keyword where a name was expected, naming the one that was found.
+fn keyword_hint(token: &TokenKind) -> String {
+    match token {
+        TokenKind::Lamda => {
+            "'λ', '\\' and 'lambda' start an abstraction, they can't be used as names".to_string()
+        }
+        TokenKind::Recursion => {
+            "'𝑓' and 'rec' start a recursion, they can't be used as names".to_string()
+        }
+        token => format!("{} is a keyword, it can't be used as a name", token),
+    }
+}
+
+const LET_HINT: &str = "a local binding is written let name = value in body";
+
 #[derive(Debug, Clone)]
 
 pub struct Program {
@@ -14,50 +40,80 @@ pub struct Program {
 pub enum Statement {
     // name = λparam. body
     // PI = 3.14
-    Binding {
-        name: String,
-        value: Expr,
-    },
+    Binding { name: String, value: Expr },
 
     // A standalone expression, e.g.:
     // (function_x) 2 - (function_t) 3
     //  (λparam. body) 2
     ExpressionStmt(Expr),
     Eof,
-    // well, I thought i could do something with comment but ig, i don't really need it huh?
-    #[allow(unused)]
-    Comment(String),
+    // Everything after the `//`. Only `lamda fmt` looks at comments, the span
+    // tells it whether the comment followed a statement on the same line.
+    Comment { text: String, span: Span },
+}
+
+// An expression along with where it was written, so runtime errors can point
+// back into the source.
+#[derive(Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+// Spans are left out, they only get in the way when dumping a tree with `:ast`.
+impl fmt::Debug for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.kind.fmt(f)
+    }
 }
 
 #[derive(Debug, Clone)]
-pub enum Expr {
-    Identifier(String),
+pub enum ExprKind {
+    Identifier {
+        name: String,
+        binding: Binding,
+    },
     Abstraction {
         // lambda abstraction: λx. body
-        param: String,
-        body: Box<Expr>,
+        param: Rc<str>,
+        // λ@x. body, x is the built-in of that name rather than a plain
+        // parameter. It's still bound as `x` in the body.
+        builtin: bool,
+        // Shared with every closure made from it.
+        body: Rc<Expr>,
     },
     Literal(f64),
-    Recursion(Box<Expr>),
-
-    // wasn't planning to add this, but ig it is kinda required
-
-    // This is a special type of application.
-    // Ths application, returns arg2 if arg1 is 1.
-    // else, the lamda is halted.
-    // arg2 is captured from surrounding.
-    // The function still receives single params which is arg1
+    // "text", with its escapes already replaced.
+    String(Rc<str>),
+    // [a, b] or [a, b, ..rest], `rest` has to evaluate to a list.
+    List {
+        elements: Vec<Expr>,
+        rest: Option<Box<Expr>>,
+    },
+    // 𝑓(arg), shared with the values that carry it to the next iteration.
+    Recursion(Rc<Expr>),
 
-    // syntax (λidentifier.expr) expr expr
-    // eg. (λprint.print) 1 10
-    // here, \n is printed since, 1 is arg1.
+    // if condition then a else b
+    // Only the branch taken is evaluated, any number other than 0 takes the
+    // first one.
+    If {
+        condition: Box<Expr>,
+        then_branch: Box<Expr>,
+        else_branch: Box<Expr>,
+    },
 
-    // Halt signal is properly descibed in interpreter > EvaluationValue
-    #[allow(unused)]
-    ApplicationIf {
-        func: Box<Expr>,
-        arg1: Box<Expr>,
-        arg2: Box<Expr>,
+    // let name = value in body
+    // `name` is only bound in the body, which runs to the end of the expression.
+    Let {
+        name: String,
+        value: Box<Expr>,
+        body: Box<Expr>,
     },
 
     Application {
@@ -71,54 +127,349 @@ pub enum Expr {
         lhs: Box<Expr>,
         rhs: Box<Expr>,
     },
+    UnaryOperation {
+        op: UnaryOp,
+        operand: Box<Expr>,
+    },
 }
 
-#[derive(Debug, Clone)]
+// Where an identifier gets its value from, filled in by the resolver.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Binding {
+    #[default]
+    Unresolved,
+    // The parameter of the abstraction `depth` levels out, 0 being the innermost
+    // one. Abstractions take a single parameter, so that's all it takes.
+    Local {
+        depth: usize,
+    },
+    Global {
+        index: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BinaryOp {
     Add,
     Sub,
     Mul,
     Div,
+    // Remainder and division rounded down, `-7 % 3` is 2 and `-7 div 3` is -3.
+    Rem,
+    IntDiv,
+    Pow,
+    Shl,
+    Shr,
     BitAnd,
     BitOr,
+    BitXor,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+}
+
+// Comparisons and logic evaluate to 1 for true and 0 for false, and take any
+// number other than 0 as true. Bitwise operators and shifts work on the
+// operands rounded toward zero, as 64-bit two's complement integers.
+// Everything else is floating point, dividing by 0 gives infinity or NaN.
+impl BinaryOp {
+    pub fn apply(self, l: f64, r: f64) -> f64 {
+        match self {
+            BinaryOp::Add => l + r,
+            BinaryOp::Sub => l - r,
+            BinaryOp::Mul => l * r,
+            BinaryOp::Div => l / r,
+            BinaryOp::Rem => {
+                let rem = l % r;
+                if rem != 0. && (rem < 0.) != (r < 0.) {
+                    rem + r
+                } else {
+                    rem
+                }
+            }
+            BinaryOp::IntDiv => (l / r).floor(),
+            BinaryOp::Pow => l.powf(r),
+            BinaryOp::Shl => shift_left(l as i64, r as i64) as f64,
+            BinaryOp::Shr => shift_right(l as i64, r as i64) as f64,
+            BinaryOp::BitAnd => ((l as i64) & (r as i64)) as f64,
+            BinaryOp::BitOr => ((l as i64) | (r as i64)) as f64,
+            BinaryOp::BitXor => ((l as i64) ^ (r as i64)) as f64,
+            BinaryOp::Equal => truth(l == r),
+            BinaryOp::NotEqual => truth(l != r),
+            BinaryOp::Less => truth(l < r),
+            BinaryOp::LessEqual => truth(l <= r),
+            BinaryOp::Greater => truth(l > r),
+            BinaryOp::GreaterEqual => truth(l >= r),
+            BinaryOp::And => truth(l != 0. && r != 0.),
+            BinaryOp::Or => truth(l != 0. || r != 0.),
+        }
+    }
+
+    pub(crate) fn precedence(self) -> Precedence {
+        match self {
+            BinaryOp::Or => Precedence::Or,
+            BinaryOp::And => Precedence::And,
+            BinaryOp::Equal
+            | BinaryOp::NotEqual
+            | BinaryOp::Less
+            | BinaryOp::LessEqual
+            | BinaryOp::Greater
+            | BinaryOp::GreaterEqual => Precedence::Comparison,
+            BinaryOp::BitOr => Precedence::BitOr,
+            BinaryOp::BitXor => Precedence::BitXor,
+            BinaryOp::BitAnd => Precedence::BitAnd,
+            BinaryOp::Shl | BinaryOp::Shr => Precedence::Shift,
+            BinaryOp::Add | BinaryOp::Sub => Precedence::Sum,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem | BinaryOp::IntDiv => Precedence::Product,
+            BinaryOp::Pow => Precedence::Power,
+        }
+    }
+}
+
+// Shifting by a negative amount shifts the other way, and shifting every bit
+// out leaves 0, or -1 for a negative number shifted right.
+fn shift_left(number: i64, amount: i64) -> i64 {
+    match amount {
+        ..0 => shift_right(number, amount.saturating_neg()),
+        0..64 => number << amount,
+        _ => 0,
+    }
+}
+
+fn shift_right(number: i64, amount: i64) -> i64 {
+    match amount {
+        ..0 => shift_left(number, amount.saturating_neg()),
+        0..64 => number >> amount,
+        _ if number < 0 => -1,
+        _ => 0,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Not,
+}
+
+impl UnaryOp {
+    pub fn apply(self, operand: f64) -> f64 {
+        match self {
+            UnaryOp::Not => truth(operand == 0.),
+        }
+    }
+}
+
+impl BinaryOp {
+    // `==` and `!=` also compare strings and lists, given whether the operands
+    // are equal. `None` for the other operators, which only take numbers.
+    pub fn apply_equality(self, equal: bool) -> Option<f64> {
+        match self {
+            BinaryOp::Equal => Some(truth(equal)),
+            BinaryOp::NotEqual => Some(truth(!equal)),
+            _ => None,
+        }
+    }
+}
+
+fn truth(condition: bool) -> f64 {
+    if condition { 1. } else { 0. }
+}
+
+// The operator as it's written in source.
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::Rem => "%",
+            BinaryOp::IntDiv => "div",
+            BinaryOp::Pow => "**",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+            BinaryOp::BitXor => "^",
+            BinaryOp::Equal => "==",
+            BinaryOp::NotEqual => "!=",
+            BinaryOp::Less => "<",
+            BinaryOp::LessEqual => "<=",
+            BinaryOp::Greater => ">",
+            BinaryOp::GreaterEqual => ">=",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+        };
+        f.write_str(symbol)
+    }
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnaryOp::Not => f.write_str("!"),
+        }
+    }
 }
 
 #[derive(PartialEq, PartialOrd)]
-enum Precedence {
+pub(crate) enum Precedence {
     Lowest,
-    Sum,     // + -
-    Product, // * /
-    Bitwise, // & |
+    Or,         // ||
+    And,        // &&
+    Comparison, // == != < <= > >=
+    BitOr,      // |
+    BitXor,     // ^
+    BitAnd,     // &
+    Shift,      // << >>
+    Sum,        // + -
+    Product,    // * / % div
+    Prefix,     // !
+    Power,      // **
     #[allow(unused)]
     Call, // function application (f x)
 }
 
 pub struct Parser {
-    tokens: Vec<TokenKind>,
+    tokens: Vec<Token>,
     bindings: HashMap<String, Expr>,
+    // Span of the most recently consumed token, errors point here.
+    last_span: Span,
+}
+
+// A parameter's name, and whether it's `@name` opting into a built-in.
+fn parameter(token: &TokenKind) -> Option<(String, bool)> {
+    match token {
+        TokenKind::Identifier(name) => Some((name.clone(), false)),
+        TokenKind::Builtin(name) => Some((name.clone(), true)),
+        _ => None,
+    }
 }
 
 impl Parser {
-    pub fn parse_program(mut tokens: Vec<TokenKind>) -> Result<Program> {
+    // Fails with the first syntax error, see `parse_recovering` for all of them.
+    pub fn parse_program(tokens: Vec<Token>) -> Result<Program> {
+        let (program, mut diagnostics) = Self::parse_recovering(tokens);
+        if diagnostics.is_empty() {
+            Ok(program)
+        } else {
+            Err(diagnostics.swap_remove(0))
+        }
+    }
+
+    // Parses as much as it can. A statement with a syntax error is left out and
+    // parsing carries on at the next statement, so every error in the source
+    // is reported at once, in order.
+    pub fn parse_recovering(mut tokens: Vec<Token>) -> (Program, Vec<Diagnostic>) {
         let mut statements: Vec<Statement> = Vec::new();
+        let mut diagnostics = Vec::new();
         tokens.reverse();
         let mut this = Self {
             tokens,
             bindings: HashMap::new(),
+            last_span: Span::default(),
         };
         while !this.tokens.is_empty() {
-            statements.push(this.parse_statement()?);
+            let remaining = this.tokens.len();
+            match this.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(diagnostic) => {
+                    diagnostics.push(diagnostic);
+                    this.synchronize(remaining);
+                }
+            }
+        }
+        (Program { statements }, diagnostics)
+    }
+
+    // Skips the rest of a broken statement: up to the next binding, or the next
+    // line that starts like a statement. `remaining` is how many tokens were
+    // left when the statement started.
+    fn synchronize(&mut self, remaining: usize) {
+        if self.tokens.len() == remaining {
+            // Nothing was consumed, skip the token that was rejected.
+            self.consume();
+        }
+        while let Some(token) = self.tokens.last() {
+            let starts_line = token.span.line > self.last_span.line;
+            let starts_statement = matches!(
+                token.kind,
+                TokenKind::Identifier(_)
+                    | TokenKind::Lamda
+                    | TokenKind::If
+                    | TokenKind::Let
+                    | TokenKind::Literal(_)
+                    | TokenKind::String(_)
+                    | TokenKind::Comment(_)
+                    | TokenKind::Operator(
+                        Operator::LeftParen | Operator::LeftBracket | Operator::Not
+                    )
+            );
+            if matches!(token.kind, TokenKind::Eof)
+                || self.at_binding()
+                || (starts_line && starts_statement)
+            {
+                break;
+            }
+            self.consume();
         }
-        Ok(Program { statements })
+    }
+
+    // Whether the next tokens are `name =`.
+    fn at_binding(&self) -> bool {
+        let mut next = self.tokens.iter().rev().map(|token| &token.kind);
+        matches!(
+            (next.next(), next.next()),
+            (
+                Some(TokenKind::Identifier(_)),
+                Some(TokenKind::Operator(Operator::Equal))
+            )
+        )
+    }
+
+    // Whether the next token starts an argument, a binding doesn't.
+    fn at_argument(&self) -> bool {
+        matches!(
+            self.look_ahead(),
+            Some(
+                TokenKind::Identifier(_)
+                    | TokenKind::Literal(_)
+                    | TokenKind::String(_)
+                    | TokenKind::Lamda
+                    | TokenKind::Recursion
+                    | TokenKind::If
+                    | TokenKind::Let
+                    | TokenKind::Operator(
+                        Operator::LeftParen | Operator::LeftBracket | Operator::Not
+                    )
+            )
+        ) && !self.at_binding()
+    }
+
+    // Whether the next token starts another argument of a multi-argument
+    // application, see the LeftParen rule in `parse_prefix`.
+    fn at_further_argument(&self) -> bool {
+        matches!(
+            self.look_ahead(),
+            Some(
+                TokenKind::Identifier(_)
+                    | TokenKind::Literal(_)
+                    | TokenKind::String(_)
+                    | TokenKind::Operator(Operator::LeftBracket)
+            )
+        ) && self.look_ahead_span().line == self.last_span.line
+            && !self.at_binding()
     }
 
     fn parse_statement(&mut self) -> Result<Statement> {
         match self.look_ahead() {
             Some(TokenKind::Identifier(_)) => {
                 // See if this is a binding: "name = ..."
-                if let Some(TokenKind::Operator(Operator::Equal)) =
-                    self.tokens.get(self.tokens.len() - 2)
-                {
+                if self.at_binding() {
                     self.parse_binding()
                 } else {
                     Ok(Statement::ExpressionStmt(
@@ -128,8 +479,11 @@ impl Parser {
             }
 
             Some(TokenKind::Lamda)
-            | Some(TokenKind::Literal(_))
-            | Some(TokenKind::Operator(Operator::LeftParen)) => Ok(Statement::ExpressionStmt(
+            | Some(TokenKind::If | TokenKind::Let)
+            | Some(TokenKind::Literal(_) | TokenKind::String(_))
+            | Some(TokenKind::Operator(
+                Operator::LeftParen | Operator::LeftBracket | Operator::Not,
+            )) => Ok(Statement::ExpressionStmt(
                 self.parse_expression(Precedence::Lowest)?,
             )),
             Some(TokenKind::Comment(_)) => self.parse_comment(),
@@ -137,27 +491,54 @@ impl Parser {
                 self.tokens.pop();
                 Ok(Statement::Eof)
             }
-            toke => bail!("Unexpected token at start of statement {:?}", toke),
+            Some(token) => {
+                let hint = match token {
+                    TokenKind::Recursion => {
+                        "𝑓(...) goes in the body of an abstraction, like λn. 𝑓(n - 1)".to_string()
+                    }
+                    TokenKind::Then | TokenKind::Else | TokenKind::In => {
+                        format!("{} is a keyword, it only continues an expression", token)
+                    }
+                    TokenKind::Operator(Operator::Div) => keyword_hint(token),
+                    _ => "a statement starts with a name, a number, a string, 'λ' (or '\\'), '(', '[' or '!'"
+                        .to_string(),
+                };
+                throw_syntax_error!(
+                    "a binding or an expression",
+                    token,
+                    self.look_ahead_span(),
+                    hint
+                )
+            }
+            None => {
+                throw_syntax_error!("a binding or an expression", TokenKind::Eof, self.last_span)
+            }
         }
     }
     fn parse_comment(&mut self) -> Result<Statement> {
-        if let Some(TokenKind::Comment(comment)) = self.consume() {
-            Ok(Statement::Comment(comment))
+        if let Some(TokenKind::Comment(text)) = self.consume() {
+            Ok(Statement::Comment {
+                text,
+                span: self.last_span,
+            })
         } else {
-            throw_syntax_error!("comment", "Unexpected token")
+            throw_syntax_error!("a comment", "something else", self.last_span)
         }
     }
 
     fn parse_binding(&mut self) -> Result<Statement> {
         // Expect identifier
-        let name = if let Some(TokenKind::Identifier(name)) = self.consume() {
-            name
-        } else {
-            bail!("Expected identifier in binding")
+        let name = match self.consume() {
+            Some(TokenKind::Identifier(name)) => name,
+            token => throw_syntax_error!(
+                "an identifier",
+                token.unwrap_or(TokenKind::Eof),
+                self.last_span
+            ),
         };
 
         // Expect '='
-        self.consume_expect(TokenKind::Operator(Operator::Equal));
+        self.consume_expect(TokenKind::Operator(Operator::Equal))?;
         // Parse the value
         let value = self.parse_expression(Precedence::Lowest)?;
         self.bindings.insert(name.clone(), value.clone());
@@ -178,39 +559,66 @@ impl Parser {
 
     fn parse_prefix(&mut self) -> Result<Expr> {
         match self.consume() {
-            Some(TokenKind::Identifier(name)) => Ok(Expr::Identifier(name.clone())),
-            Some(TokenKind::Literal(number)) => Ok(Expr::Literal(number.clone())),
+            Some(TokenKind::Identifier(name)) => Ok(Expr::new(
+                ExprKind::Identifier {
+                    name,
+                    binding: Binding::Unresolved,
+                },
+                self.last_span,
+            )),
+            Some(TokenKind::Literal(number)) => {
+                Ok(Expr::new(ExprKind::Literal(number), self.last_span))
+            }
+            Some(TokenKind::String(text)) => {
+                Ok(Expr::new(ExprKind::String(text.into()), self.last_span))
+            }
             Some(TokenKind::Lamda) => self.parse_abstraction(),
             Some(TokenKind::Recursion) => self.parse_recursion(),
+            Some(TokenKind::If) => self.parse_if(),
+            Some(TokenKind::Let) => self.parse_let(),
+            Some(TokenKind::Operator(Operator::LeftBracket)) => self.parse_list(),
+            Some(TokenKind::Operator(Operator::Not)) => {
+                let start = self.last_span;
+                let operand = self.parse_expression(Precedence::Prefix)?;
+                let span = start.to(operand.span);
+                Ok(Expr::new(
+                    ExprKind::UnaryOperation {
+                        op: UnaryOp::Not,
+                        operand: Box::new(operand),
+                    },
+                    span,
+                ))
+            }
             Some(TokenKind::Operator(Operator::LeftParen)) => {
-                let expr = self.parse_expression(Precedence::Call)?;
-                self.consume_expect(TokenKind::Operator(Operator::RightParen));
-                Ok(Expr::Application {
-                    func: Box::new(expr),
-                    arg: Box::new(self.parse_expression(Precedence::Lowest)?),
-                })
-
-                // Removed applicationIf support.
-
-                // let expr = self.parse_expression(Precedence::Call)?;
-
-                // let arg1 = self.parse_expression(Precedence::Lowest)?;
-
-                // if self.look_expect(TokenKind::Operator(Operator::RightParen)) {
-                //     self.consume_expect(TokenKind::Operator(Operator::RightParen));
-                //     Ok(Expr::Application {
-                //         func: Box::new(expr),
-                //         arg: Box::new(arg1),
-                //     })
-                // } else {
-                //     let arg2 = self.parse_expression(Precedence::Lowest)?;
-                //     self.consume_expect(TokenKind::Operator(Operator::RightParen));
-                //     Ok(Expr::ApplicationIf {
-                //         func: Box::new(expr),
-                //         arg1: Box::new(arg1),
-                //         arg2: Box::new(arg2),
-                //     })
-                // }
+                let start = self.last_span;
+                let expr = self.parse_expression(Precedence::Lowest)?;
+                self.consume_expect(TokenKind::Operator(Operator::RightParen))?;
+                // `(a + b) * c` only groups. Anything else is applied when an
+                // argument follows, so `(f) x` reads the same as it always has.
+                let operation = matches!(
+                    expr.kind,
+                    ExprKind::BinaryOperation { .. } | ExprKind::UnaryOperation { .. }
+                );
+                if operation || !self.at_argument() {
+                    return Ok(expr);
+                }
+                // `(f) 2 10` is `((f) 2) 10`. Only an argument that starts on
+                // the same line with a name, a number, a string or a list is
+                // another one, so `(f) 2 (g) 3` stays two applications.
+                let mut application = expr;
+                loop {
+                    let arg = self.parse_expression(Precedence::Lowest)?;
+                    application = Expr::new(
+                        ExprKind::Application {
+                            func: Box::new(application),
+                            arg: Box::new(arg),
+                        },
+                        start.to(self.last_span),
+                    );
+                    if !self.at_further_argument() {
+                        return Ok(application);
+                    }
+                }
             }
             // +------------------------------------------------------------------------------------------------------------------------------------------+
             // |The original plan was to incorporate both applied functions and the BODMAS/PENDAS rule                                                    |
@@ -278,7 +686,11 @@ impl Parser {
             //         expr => expr,
             //     }
             // }
-            e => bail!("Unexpected token in prefix position {:?}", e),
+            token => throw_syntax_error!(
+                "an expression",
+                token.unwrap_or(TokenKind::Eof),
+                self.last_span
+            ),
         }
     }
 
@@ -292,106 +704,408 @@ impl Parser {
     //     }
     // }
 
-    fn consume_expect(&mut self, expected: TokenKind) {
-        if let Some(token) = self.consume() {
-            if token != expected {
-                throw_syntax_error!(format!("{:?}", token), format!("{:?}", expected));
-            }
-        } else {
-            throw_syntax_error!(format!("{:?}", expected), "None");
+    fn consume_expect(&mut self, expected: TokenKind) -> Result<()> {
+        match self.consume() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => throw_syntax_error!(expected, token, self.last_span),
+            None => throw_syntax_error!(expected, TokenKind::Eof, self.last_span),
         }
     }
-    
+
+    fn consume_expect_with_hint(&mut self, expected: TokenKind, hint: &str) -> Result<()> {
+        self.consume_expect(expected)
+            .map_err(|diagnostic| diagnostic.with_hint(hint))
+    }
+
     #[allow(unused)]
-    fn look_expect(&self, expected: TokenKind) -> bool {
+    fn look_expect(&self, expected: TokenKind) -> Result<bool> {
         match self.look_ahead() {
-            Some(token) if *token == expected => true,
-            Some(_) => false,
-            None => throw_syntax_error!(format!("{:?}", expected), "None"),
+            Some(token) => Ok(*token == expected),
+            None => throw_syntax_error!(expected, TokenKind::Eof, self.last_span),
         }
     }
 
+    // `λx y z. body` is `λx. λy. λz. body`, the inner abstractions start at
+    // their parameter.
     fn parse_abstraction(&mut self) -> Result<Expr> {
-        if let Some(TokenKind::Identifier(param)) = self.consume() {
-            self.consume_expect(TokenKind::Operator(Operator::Dot));
-            let body = self.parse_expression(Precedence::Lowest);
-            Ok(Expr::Abstraction {
-                param: param,
-                body: Box::new(body?),
-            })
-        } else {
-            throw_syntax_error!("parameter", "none")
+        let start = self.last_span;
+        let mut params = Vec::new();
+        while let Some((param, builtin)) = self.look_ahead().and_then(parameter) {
+            self.consume();
+            // The outermost abstraction starts at its λ.
+            let span = if params.is_empty() {
+                start
+            } else {
+                self.last_span
+            };
+            params.push((param, builtin, span));
+        }
+        if params.is_empty() {
+            match self.consume() {
+                Some(
+                    token @ (TokenKind::Lamda
+                    | TokenKind::Recursion
+                    | TokenKind::If
+                    | TokenKind::Then
+                    | TokenKind::Else
+                    | TokenKind::Let
+                    | TokenKind::In
+                    | TokenKind::Operator(Operator::Div)),
+                ) => {
+                    let hint = keyword_hint(&token);
+                    throw_syntax_error!("a parameter name", token, self.last_span, hint)
+                }
+                token => throw_syntax_error!(
+                    "a parameter name",
+                    token.unwrap_or(TokenKind::Eof),
+                    self.last_span,
+                    ABSTRACTION_HINT
+                ),
+            }
+        }
+        self.consume_expect_with_hint(TokenKind::Operator(Operator::Dot), ABSTRACTION_HINT)?;
+        let mut body = self.parse_expression(Precedence::Lowest)?;
+        for (param, builtin, start) in params.into_iter().rev() {
+            body = Expr::new(
+                ExprKind::Abstraction {
+                    param: param.into(),
+                    builtin,
+                    body: Rc::new(body),
+                },
+                start.to(self.last_span),
+            );
         }
+        Ok(body)
     }
     fn parse_recursion(&mut self) -> Result<Expr> {
-        if let Some(TokenKind::Operator(Operator::LeftParen)) = self.consume() {
-            let body = self.parse_expression(Precedence::Lowest);
-            self.consume_expect(TokenKind::Operator(Operator::RightParen));
-            Ok(Expr::Recursion(Box::new(body?)))
-        } else {
-            throw_syntax_error!("parameter", "none")
+        let start = self.last_span;
+        match self.consume() {
+            Some(TokenKind::Operator(Operator::LeftParen)) => {
+                let body = self.parse_expression(Precedence::Lowest)?;
+                self.consume_expect(TokenKind::Operator(Operator::RightParen))?;
+                Ok(Expr::new(
+                    ExprKind::Recursion(Rc::new(body)),
+                    start.to(self.last_span),
+                ))
+            }
+            token => throw_syntax_error!(
+                TokenKind::Operator(Operator::LeftParen),
+                token.unwrap_or(TokenKind::Eof),
+                self.last_span,
+                "recursion is written 𝑓(expression), or rec(expression)"
+            ),
+        }
+    }
+    // Elements are separated by commas and a trailing comma is fine. `..` in
+    // front of the last element puts the elements before it in front of it.
+    fn parse_list(&mut self) -> Result<Expr> {
+        let start = self.last_span;
+        let mut elements = Vec::new();
+        let mut rest = None;
+        while self.look_ahead() != Some(&TokenKind::Operator(Operator::RightBracket)) {
+            if self.look_ahead() == Some(&TokenKind::Operator(Operator::Dot)) {
+                self.consume();
+                self.consume_expect_with_hint(TokenKind::Operator(Operator::Dot), LIST_HINT)?;
+                rest = Some(Box::new(self.parse_expression(Precedence::Lowest)?));
+                break;
+            }
+            elements.push(self.parse_expression(Precedence::Lowest)?);
+            if self.look_ahead() != Some(&TokenKind::Operator(Operator::Comma)) {
+                break;
+            }
+            self.consume();
         }
+        self.consume_expect_with_hint(TokenKind::Operator(Operator::RightBracket), LIST_HINT)?;
+        Ok(Expr::new(
+            ExprKind::List { elements, rest },
+            start.to(self.last_span),
+        ))
     }
+
+    fn parse_let(&mut self) -> Result<Expr> {
+        let start = self.last_span;
+        let name = match self.consume() {
+            Some(TokenKind::Identifier(name)) => name,
+            Some(
+                token @ (TokenKind::Lamda
+                | TokenKind::Recursion
+                | TokenKind::If
+                | TokenKind::Then
+                | TokenKind::Else
+                | TokenKind::Let
+                | TokenKind::In
+                | TokenKind::Operator(Operator::Div)),
+            ) => {
+                let hint = keyword_hint(&token);
+                throw_syntax_error!("a name", token, self.last_span, hint)
+            }
+            token => throw_syntax_error!(
+                "a name",
+                token.unwrap_or(TokenKind::Eof),
+                self.last_span,
+                LET_HINT
+            ),
+        };
+        self.consume_expect_with_hint(TokenKind::Operator(Operator::Equal), LET_HINT)?;
+        let value = self.parse_expression(Precedence::Lowest)?;
+        self.consume_expect_with_hint(TokenKind::In, LET_HINT)?;
+        let body = self.parse_expression(Precedence::Lowest)?;
+        Ok(Expr::new(
+            ExprKind::Let {
+                name,
+                value: Box::new(value),
+                body: Box::new(body),
+            },
+            start.to(self.last_span),
+        ))
+    }
+
+    // The branches run to the next `else` and to the end of the expression,
+    // like the body of an abstraction.
+    fn parse_if(&mut self) -> Result<Expr> {
+        let start = self.last_span;
+        let condition = self.parse_expression(Precedence::Lowest)?;
+        self.consume_expect_with_hint(TokenKind::Then, IF_HINT)?;
+        let then_branch = self.parse_expression(Precedence::Lowest)?;
+        self.consume_expect_with_hint(TokenKind::Else, IF_HINT)?;
+        let else_branch = self.parse_expression(Precedence::Lowest)?;
+        Ok(Expr::new(
+            ExprKind::If {
+                condition: Box::new(condition),
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
+            },
+            start.to(self.last_span),
+        ))
+    }
+
     fn parse_infix(&mut self, left: Expr) -> Result<Expr> {
-        match self.consume() {
-            Some(TokenKind::Operator(Operator::Plus)) => Ok(Expr::BinaryOperation {
-                op: BinaryOp::Add,
-                lhs: Box::new(left),
-                rhs: Box::new(self.parse_expression(Precedence::Sum)?),
-            }),
-            Some(TokenKind::Operator(Operator::Minus)) => Ok(Expr::BinaryOperation {
-                op: BinaryOp::Sub,
-                lhs: Box::new(left),
-                rhs: Box::new(self.parse_expression(Precedence::Sum)?),
-            }),
-            Some(TokenKind::Operator(Operator::Asterisk)) => Ok(Expr::BinaryOperation {
-                op: BinaryOp::Mul,
-                lhs: Box::new(left),
-                rhs: Box::new(self.parse_expression(Precedence::Product)?),
-            }),
-            Some(TokenKind::Operator(Operator::Slash)) => Ok(Expr::BinaryOperation {
-                op: BinaryOp::Div,
-                lhs: Box::new(left),
-                rhs: Box::new(self.parse_expression(Precedence::Product)?),
-            }),
-            Some(TokenKind::Operator(Operator::BitAnd)) => Ok(Expr::BinaryOperation {
-                op: BinaryOp::BitAnd,
-                lhs: Box::new(left),
-                rhs: Box::new(self.parse_expression(Precedence::Bitwise)?),
-            }),
-            Some(TokenKind::Operator(Operator::BitOr)) => Ok(Expr::BinaryOperation {
-                op: BinaryOp::BitOr,
+        let token = self.consume();
+        let Some(op) = token.as_ref().and_then(binary_op) else {
+            throw_syntax_error!(
+                "an operator",
+                token.unwrap_or(TokenKind::Eof),
+                self.last_span,
+                "did you forget to pass parameter to application?"
+            );
+        };
+        // `**` groups to the right, so its right operand may hold another one.
+        let precedence = match op {
+            BinaryOp::Pow => Precedence::Prefix,
+            op => op.precedence(),
+        };
+        let rhs = self.parse_expression(precedence)?;
+        let span = left.span.to(rhs.span);
+        Ok(Expr::new(
+            ExprKind::BinaryOperation {
+                op,
                 lhs: Box::new(left),
-                rhs: Box::new(self.parse_expression(Precedence::Bitwise)?),
-            }),
-            _ => bail!(
-                "Unexpected token in infix position, Did you forget to pass parameter to application?"
-            ),
-        }
+                rhs: Box::new(rhs),
+            },
+            span,
+        ))
     }
 
     fn look_ahead(&self) -> Option<&TokenKind> {
-        self.tokens.last()
+        self.tokens.last().map(|token| &token.kind)
+    }
+
+    fn look_ahead_span(&self) -> Span {
+        self.tokens
+            .last()
+            .map_or(self.last_span, |token| token.span)
     }
 
     fn consume(&mut self) -> Option<TokenKind> {
-        self.tokens.pop()
+        let token = self.tokens.pop()?;
+        self.last_span = token.span;
+        Some(token.kind)
     }
 
+    // How tightly an infix operator binds, from loosest to tightest:
+    //
+    //   ||                          logical or
+    //   &&                          logical and
+    //   ==  !=  <  <=  >  >=        comparison
+    //   |                           bitwise or
+    //   ^                           bitwise xor
+    //   &                           bitwise and
+    //   <<  >>                      shifts
+    //   +  -                        sum
+    //   *  /  %  div                product
+    //   !                           prefix, binds tighter than any of the above
+    //   **                          power, `!x ** 2` is `!(x ** 2)`
+    //
+    // Everything groups to the left except `**`, `2 ** 3 ** 2` is `2 ** 9`.
+    // Bitwise operators used to bind tighter than `*`, so `a * b & c` was
+    // `a * (b & c)`, it is `(a * b) & c` now.
     fn get_precedence(&self, token: &TokenKind) -> Precedence {
-        match token {
-            TokenKind::Operator(Operator::Plus) | TokenKind::Operator(Operator::Minus) => {
-                Precedence::Sum
-            }
-            TokenKind::Operator(Operator::Asterisk) | TokenKind::Operator(Operator::Slash) => {
-                Precedence::Product
-            }
-            TokenKind::Operator(Operator::BitAnd) | TokenKind::Operator(Operator::BitOr) => {
-                Precedence::Bitwise
-            }
-            // TokenKind::Operator(Operator::LeftParen) => Precedence::Lowest,
-            // TokenKind::Operator(Operator::LeftParen) => Precedence::Lowest,
-            _ => Precedence::Lowest,
+        binary_op(token).map_or(Precedence::Lowest, BinaryOp::precedence)
+    }
+}
+
+fn binary_op(token: &TokenKind) -> Option<BinaryOp> {
+    let TokenKind::Operator(operator) = token else {
+        return None;
+    };
+    let op = match operator {
+        Operator::Plus => BinaryOp::Add,
+        Operator::Minus => BinaryOp::Sub,
+        Operator::Asterisk => BinaryOp::Mul,
+        Operator::Slash => BinaryOp::Div,
+        Operator::Percent => BinaryOp::Rem,
+        Operator::Div => BinaryOp::IntDiv,
+        Operator::DoubleAsterisk => BinaryOp::Pow,
+        Operator::ShiftLeft => BinaryOp::Shl,
+        Operator::ShiftRight => BinaryOp::Shr,
+        Operator::BitAnd => BinaryOp::BitAnd,
+        Operator::Caret => BinaryOp::BitXor,
+        Operator::BitOr => BinaryOp::BitOr,
+        Operator::EqualEqual => BinaryOp::Equal,
+        Operator::NotEqual => BinaryOp::NotEqual,
+        Operator::Less => BinaryOp::Less,
+        Operator::LessEqual => BinaryOp::LessEqual,
+        Operator::Greater => BinaryOp::Greater,
+        Operator::GreaterEqual => BinaryOp::GreaterEqual,
+        Operator::And => BinaryOp::And,
+        Operator::Or => BinaryOp::Or,
+        _ => return None,
+    };
+    Some(op)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    #[test]
+    fn recovers_at_the_next_statement() {
+        let source = "x = (λa a) 1\ny = λ. 2\n(λ@print. y) 0\nz = ) 3\n(λ@ascii. ascii) 10";
+        let (program, diagnostics) =
+            Parser::parse_recovering(Lexer::new(source).get_tokens().unwrap());
+        let lines = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.span.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [1, 2, 4]);
+        // The statements after each error are still there.
+        let parsed = program
+            .statements
+            .iter()
+            .filter(|statement| !matches!(statement, Statement::Eof))
+            .count();
+        assert_eq!(parsed, 2);
+    }
+
+    #[test]
+    fn parentheses_group_unless_an_argument_follows() {
+        let source = "a = 2\nb = 3\n(a + b) * 4\na - (b - 1)\n((λx. x * 10) a) + 1\n\
+                      ((a)) * (λx. x) b\nc = (a)\nd = c + 1";
+        let results = crate::run_str(source, crate::MemoryHost::new(""))
+            .unwrap()
+            .iter()
+            .filter(|value| !matches!(value, crate::EvaluationValue::Unit))
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(results, ["2", "3", "20", "0", "21", "6", "2", "3"]);
+    }
+
+    // The statements of `source`, without their spans, to compare two
+    // spellings of the same program.
+    fn parse(source: &str) -> String {
+        let program = Parser::parse_program(Lexer::new(source).get_tokens().unwrap()).unwrap();
+        format!("{:?}", program.statements)
+    }
+
+    #[test]
+    fn operators_follow_the_precedence_table() {
+        for (source, grouped) in [
+            ("x = a || b && c", "x = a || (b && c)"),
+            ("x = a && b == c", "x = a && (b == c)"),
+            ("x = a == b | c", "x = a == (b | c)"),
+            ("x = a | b ^ c", "x = a | (b ^ c)"),
+            ("x = a ^ b & c", "x = a ^ (b & c)"),
+            ("x = a & b << c", "x = a & (b << c)"),
+            ("x = a << b + c", "x = a << (b + c)"),
+            ("x = a - b div c", "x = a - (b div c)"),
+            ("x = a * !b", "x = a * (!b)"),
+            ("x = !a ** b", "x = !(a ** b)"),
+            ("x = a - b - c", "x = (a - b) - c"),
+            ("x = a % b * c", "x = (a % b) * c"),
+            ("x = a == b == c", "x = (a == b) == c"),
+            ("x = a ** b ** c", "x = a ** (b ** c)"),
+        ] {
+            assert_eq!(parse(source), parse(grouped), "{}", source);
         }
     }
+
+    #[test]
+    fn bodies_and_branches_run_to_the_end_of_the_expression() {
+        assert_eq!(parse("f = λx. x + 1"), parse("f = λx. (x + 1)"));
+        assert_eq!(
+            parse("x = if a then b else c + 1"),
+            parse("x = if a then b else (c + 1)")
+        );
+        assert_eq!(
+            parse("x = let a = 1 in a + 1"),
+            parse("x = let a = 1 in (a + 1)")
+        );
+        assert_ne!(
+            parse("x = (if a then b else c) + 1"),
+            parse("x = if a then b else (c + 1)")
+        );
+    }
+
+    #[test]
+    fn several_parameters_and_arguments_are_sugar() {
+        assert_eq!(
+            parse("f = λx y z. x\n(f) 1 b [2] \"c\""),
+            parse("f = λx. λy. λz. x\n((((f) 1) b) [2]) \"c\"")
+        );
+        // Anything else after the first argument starts a new statement.
+        assert_eq!(
+            parse("(f) 1 (g) 2\n(f) 1\n2 (f) 1 !2"),
+            parse("(f) 1\n(g) 2\n(f) 1\n2\n(f) 1\n!2")
+        );
+    }
+
+    #[test]
+    fn keywords_used_as_names_are_named_in_the_hint() {
+        let hint = |source: &str| {
+            Parser::parse_program(Lexer::new(source).get_tokens().unwrap())
+                .unwrap_err()
+                .hint
+                .clone()
+                .unwrap()
+        };
+        assert_eq!(
+            hint("f = λ𝑓x. x"),
+            "'𝑓' and 'rec' start a recursion, they can't be used as names"
+        );
+        assert_eq!(
+            hint("f = λlambda. 1"),
+            "'λ', '\\' and 'lambda' start an abstraction, they can't be used as names"
+        );
+        assert_eq!(
+            hint("𝑓(1)"),
+            "𝑓(...) goes in the body of an abstraction, like λn. 𝑓(n - 1)"
+        );
+        assert_eq!(
+            hint("f = λdiv. 1"),
+            "'div' is a keyword, it can't be used as a name"
+        );
+        assert_eq!(
+            hint("div = 3"),
+            "'div' is a keyword, it can't be used as a name"
+        );
+        assert_eq!(
+            hint("let in = 2 in 3"),
+            "'in' is a keyword, it can't be used as a name"
+        );
+        assert_eq!(
+            hint("then 2"),
+            "'then' is a keyword, it only continues an expression"
+        );
+    }
 }
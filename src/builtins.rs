@@ -0,0 +1,265 @@
+// Built-in abstractions are looked up by parameter name in a BuiltinRegistry.
+//
+// The interpreter only knows how to hand a built-in its argument, what the built-in
+// accepts and what it does lives here, so host code can register its own without
+// touching the interpreter core.
+
+use super::abstractions;
+use super::host::Host;
+use super::interpreter::EvaluationValue;
+use super::list::{Element, List};
+
+use anyhow::{Result, bail};
+use std::{collections::HashMap, rc::Rc};
+
+// What a built-in accepts as the value its body evaluated to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Argument {
+    // Anything, the handler is passed 0, e.g. λ@time.
+    Ignored,
+    Number,
+    // A number in `start..=end`.
+    Range(f64, f64),
+    // One of a fixed set of numbers, e.g. the modes of λ@input.
+    OneOf(&'static [f64]),
+    // A whole number that is a Unicode scalar value, e.g. λ@char.
+    CodePoint,
+    // A string, e.g. λ@write. Only built-ins made with `Builtin::text` take one.
+    Text,
+    // A list, e.g. λ@length. Only built-ins made with `Builtin::list` take one.
+    List,
+}
+
+impl Argument {
+    // The number handed to the handler, `None` when the value is not accepted.
+    // `number` is the value if it was a number at all.
+    pub fn check(&self, number: Option<f64>) -> Option<f64> {
+        match (self, number) {
+            (Argument::Ignored, number) => Some(number.unwrap_or(0.)),
+            (Argument::Number, Some(number)) => Some(number),
+            (Argument::Range(start, end), Some(number)) if (*start..=*end).contains(&number) => {
+                Some(number)
+            }
+            (Argument::CodePoint, Some(number))
+                if number.fract() == 0.
+                    && number >= 0.
+                    && char::from_u32(number as u32).is_some() =>
+            {
+                Some(number)
+            }
+            (Argument::OneOf(choices), Some(number)) if choices.contains(&number) => Some(number),
+            // Strings and lists don't go through here, see `Builtin::call_text`
+            // and `Builtin::list_op`.
+            _ => None,
+        }
+    }
+}
+
+// What a list built-in does. Lists hold the values of whichever backend is
+// running, so it's done through `list::Element`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListOp {
+    // The first element.
+    Head,
+    // Everything after the first element.
+    Tail,
+    Length,
+}
+
+type NumberHandler = dyn Fn(&mut dyn Host, f64) -> Result<EvaluationValue>;
+type TextHandler = dyn Fn(&mut dyn Host, &str) -> Result<EvaluationValue>;
+
+enum Handler {
+    Number(Box<NumberHandler>),
+    Text(Box<TextHandler>),
+    List(ListOp),
+}
+
+pub struct Builtin {
+    pub argument: Argument,
+    // Completes "λname only takes ..." when the argument is rejected.
+    pub expects: &'static str,
+    handler: Handler,
+}
+
+impl Builtin {
+    pub fn new(
+        argument: Argument,
+        expects: &'static str,
+        handler: impl Fn(&mut dyn Host, f64) -> Result<EvaluationValue> + 'static,
+    ) -> Self {
+        Self {
+            argument,
+            expects,
+            handler: Handler::Number(Box::new(handler)),
+        }
+    }
+
+    // A built-in taking a string, its argument is `Argument::Text`.
+    pub fn text(
+        expects: &'static str,
+        handler: impl Fn(&mut dyn Host, &str) -> Result<EvaluationValue> + 'static,
+    ) -> Self {
+        Self {
+            argument: Argument::Text,
+            expects,
+            handler: Handler::Text(Box::new(handler)),
+        }
+    }
+
+    // A built-in taking a list, its argument is `Argument::List`.
+    pub fn list(op: ListOp, expects: &'static str) -> Self {
+        Self {
+            argument: Argument::List,
+            expects,
+            handler: Handler::List(op),
+        }
+    }
+
+    // `argument` has been through `Argument::check`.
+    pub fn call(&self, host: &mut dyn Host, argument: f64) -> Result<EvaluationValue> {
+        match &self.handler {
+            Handler::Number(handler) => handler(host, argument),
+            Handler::Text(_) => bail!("expected a string, not a number"),
+            Handler::List(_) => bail!("expected a list, not a number"),
+        }
+    }
+
+    pub fn call_text(&self, host: &mut dyn Host, text: &str) -> Result<EvaluationValue> {
+        match &self.handler {
+            Handler::Text(handler) => handler(host, text),
+            Handler::Number(_) => bail!("expected a number, not a string"),
+            Handler::List(_) => bail!("expected a list, not a string"),
+        }
+    }
+
+    // `None` for built-ins not made with `Builtin::list`, and for the head or
+    // tail of an empty list, which are rejected like any other argument.
+    pub fn call_list<T: Element>(&self, list: &List<T>) -> Option<T> {
+        let Handler::List(op) = self.handler else {
+            return None;
+        };
+        match op {
+            ListOp::Head => list.head().cloned(),
+            ListOp::Tail => list.tail().map(T::list),
+            ListOp::Length => Some(T::number(list.len() as f64)),
+        }
+    }
+}
+
+impl std::fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Builtin")
+            .field("argument", &self.argument)
+            .finish_non_exhaustive()
+    }
+}
+
+// An abstraction opting into a built-in with `λ@name.` gets the one registered
+// here under `name`: once its body is evaluated the result goes to the handler.
+// Every other abstraction returns its body unchanged, whatever its parameter is
+// called. Opting into a name that isn't registered is an error.
+#[derive(Debug, Default)]
+pub struct BuiltinRegistry {
+    builtins: HashMap<String, Rc<Builtin>>,
+}
+
+impl BuiltinRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // λ@ascii, λ@char, λ@input, λ@print, λ@write, λ@time, λ@sleep, λ@head,
+    // λ@tail and λ@length.
+    pub fn standard() -> Self {
+        let mut registry = Self::new();
+        registry.register(
+            "ascii",
+            Builtin::new(
+                Argument::Range(0., 255.),
+                "ASCII values in decimal form, ranging from 0 to 255.",
+                |host, ascii| abstractions::abstraction_ascii(host, ascii as u8),
+            ),
+        );
+        registry.register(
+            "char",
+            Builtin::new(
+                Argument::CodePoint,
+                "Unicode code points in decimal form, from 0 to 1114111 except the surrogates 55296 to 57343.",
+                |host, code_point| abstractions::abstraction_char(host, code_point as u32),
+            ),
+        );
+        registry.register(
+            "input",
+            Builtin::new(
+                Argument::OneOf(&[0., 1., 2.]),
+                "numeric value either, 0, 1, or 2.",
+                |host, mode| match mode {
+                    0. => abstractions::abstraction_input_char(host),
+                    1. => abstractions::abstraction_input_numeric(host),
+                    _ => abstractions::abstraction_input_code_point(host),
+                },
+            ),
+        );
+        registry.register(
+            "time",
+            Builtin::new(Argument::Ignored, "any value.", |host, _| {
+                abstractions::abstraction_time(host)
+            }),
+        );
+        registry.register(
+            "print",
+            Builtin::new(
+                Argument::Number,
+                "numeric value.",
+                abstractions::abstraction_print,
+            ),
+        );
+        registry.register(
+            "write",
+            Builtin::text("a string.", abstractions::abstraction_write),
+        );
+        registry.register(
+            "sleep",
+            Builtin::new(
+                Argument::Number,
+                "numeric value.",
+                abstractions::abstraction_sleep,
+            ),
+        );
+        registry.register(
+            "head",
+            Builtin::list(ListOp::Head, "a list that isn't empty."),
+        );
+        registry.register(
+            "tail",
+            Builtin::list(ListOp::Tail, "a list that isn't empty."),
+        );
+        registry.register("length", Builtin::list(ListOp::Length, "a list."));
+        registry
+    }
+
+    // Replaces any built-in already registered under `name`.
+    pub fn register(&mut self, name: impl Into<String>, builtin: Builtin) {
+        self.builtins.insert(name.into(), Rc::new(builtin));
+    }
+
+    pub fn unregister(&mut self, name: &str) -> Option<Rc<Builtin>> {
+        self.builtins.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rc<Builtin>> {
+        self.builtins.get(name).cloned()
+    }
+
+    // Sorted and written the way a program opts into them, `@print`.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = self
+            .builtins
+            .keys()
+            .map(|name| format!("@{}", name))
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+}
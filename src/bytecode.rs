@@ -0,0 +1,313 @@
+// Compiles a resolved Program into bytecode for the VM in vm.rs.
+//
+// Every abstraction becomes a Function. Its parameter and the names bound by
+// `let` inside it live in the frame, the ones of enclosing abstractions it uses
+// are copied into the closure when the closure is created, and globals use the
+// resolver's slots.
+
+use super::ast::{BinaryOp, Binding, Expr, ExprKind, Statement, UnaryOp};
+use super::builtins::{Builtin, BuiltinRegistry};
+use super::error::Span;
+
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Number(f64),
+    // Pushes `strings[n]`.
+    String(u32),
+    // The parameter of the running abstraction.
+    Param,
+    // The value of an enclosing `let` in the running function, the first
+    // one is 0.
+    Local(u32),
+    Capture(u32),
+    Global(u32),
+    // Leaves the value on the stack, it is also the statement's result.
+    SetGlobal(u32),
+    // Creates a closure over `functions[n]`.
+    Closure(u32),
+    // Wraps `functions[n]`, the argument of 𝑓, without running it.
+    Recursion(u32),
+    // The right operand ends right before this op, the left one ends at the
+    // op with the given index. Errors point at whichever isn't a number.
+    Binary(BinaryOp, u32),
+    // The operand ends right before this op.
+    Unary(UnaryOp),
+    // Collects the given number of elements from the stack into a list. With
+    // the flag set they go in front of the list on top of them, which ends
+    // right before this op.
+    List(u32, bool),
+    // Pops the argument and the function, the function ends at the given index.
+    Apply(u32),
+    // Pops the condition of an `if`. Jumps to the else branch at the first
+    // index when it's 0, and past the whole `if` with HALT when it's HALT.
+    Branch(u32, u32),
+    Jump(u32),
+    // Pops the value of a `let` into the frame for its body, which ends
+    // right before the EndLet that drops it again.
+    Let,
+    EndLet,
+
+    // Ends an abstraction body that evaluates to `𝑓(...)` directly, with the
+    // argument evaluated in place instead of wrapped with Recursion. Checks
+    // it's a number or HALT and keeps it for the next iteration.
+    Next,
+    // Ends every abstraction. Runs the 𝑓 argument the body evaluated to, if it
+    // did, and comes back here with its result. Then returns HALT, or hands the
+    // value to the built-in if there is one and starts the next iteration if
+    // 𝑓 asked for one, returning otherwise.
+    End,
+    // Ends statements and the 𝑓 arguments End runs.
+    Return,
+}
+
+// Where a closure gets a captured value from, relative to the frame creating it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capture {
+    Param,
+    Local(u32),
+    // One the creating frame captured itself.
+    Captured(u32),
+}
+
+#[derive(Debug, Default)]
+pub struct Function {
+    // `None` for statements and the arguments of 𝑓.
+    pub param: Option<Rc<str>>,
+    pub code: Vec<Op>,
+    // Where each op came from, the last op of an expression carries the span
+    // of the whole expression.
+    pub spans: Vec<Span>,
+    pub functions: Vec<Rc<Function>>,
+    pub strings: Vec<Rc<str>>,
+    pub captures: Vec<Capture>,
+    pub builtin: Option<Rc<Builtin>>,
+    pub span: Span,
+}
+
+impl Function {
+    fn new(param: Option<Rc<str>>, span: Span) -> Self {
+        Self {
+            param,
+            span,
+            ..Self::default()
+        }
+    }
+}
+
+// A function being compiled, with the depths its captures stand for, counted
+// from the function itself, and how many of its `let`s the code being compiled
+// is inside of.
+struct Scope {
+    function: Function,
+    captured: Vec<usize>,
+    locals: usize,
+}
+
+#[derive(Default)]
+pub struct Compiler {
+    // Innermost last, the first one is the statement.
+    scopes: Vec<Scope>,
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // `None` for statements that evaluate to Unit without running anything.
+    // `slot` is the global a binding statement binds.
+    pub fn compile_statement(
+        &mut self,
+        statement: &Statement,
+        slot: Option<usize>,
+        builtins: &BuiltinRegistry,
+    ) -> Option<Rc<Function>> {
+        let expr = match statement {
+            Statement::Binding { value, .. } => value,
+            Statement::ExpressionStmt(expr) => expr,
+            Statement::Comment { .. } | Statement::Eof => return None,
+        };
+        self.scopes.push(Scope {
+            function: Function::new(None, expr.span),
+            captured: Vec::new(),
+            locals: 0,
+        });
+        self.compile_expr(expr, builtins);
+        if let Some(slot) = slot {
+            self.emit(Op::SetGlobal(slot as u32), expr.span);
+        }
+        self.emit(Op::Return, expr.span);
+        let scope = self.scopes.pop().expect("statement scope");
+        Some(Rc::new(scope.function))
+    }
+
+    fn compile_expr(&mut self, expr: &Expr, builtins: &BuiltinRegistry) {
+        self.compile(expr, false, builtins);
+    }
+
+    // `tail` is set for the body of an abstraction and for whatever of it
+    // evaluates to the body's value, the branches of an `if` and the body of a
+    // `let`. An 𝑓 there is run right away by End anyway, so its argument is
+    // evaluated in place rather than captured into a closure.
+    fn compile(&mut self, expr: &Expr, tail: bool, builtins: &BuiltinRegistry) {
+        match &expr.kind {
+            ExprKind::Literal(number) => self.emit(Op::Number(*number), expr.span),
+            ExprKind::String(text) => {
+                let strings = &mut self.current().strings;
+                strings.push(Rc::clone(text));
+                let index = strings.len() as u32 - 1;
+                self.emit(Op::String(index), expr.span);
+            }
+            ExprKind::Identifier { binding, .. } => {
+                let op = match binding {
+                    Binding::Local { depth } => match self.capture(*depth, self.scopes.len() - 1) {
+                        Capture::Param => Op::Param,
+                        Capture::Local(index) => Op::Local(index),
+                        Capture::Captured(index) => Op::Capture(index),
+                    },
+                    Binding::Global { index } => Op::Global(*index as u32),
+                    Binding::Unresolved => {
+                        unreachable!("identifiers are resolved before compiling")
+                    }
+                };
+                self.emit(op, expr.span);
+            }
+            ExprKind::BinaryOperation { op, lhs, rhs } => {
+                self.compile_expr(lhs, builtins);
+                let lhs_end = self.last_op();
+                self.compile_expr(rhs, builtins);
+                self.emit(Op::Binary(*op, lhs_end), expr.span);
+            }
+            ExprKind::UnaryOperation { op, operand } => {
+                self.compile_expr(operand, builtins);
+                self.emit(Op::Unary(*op), expr.span);
+            }
+            ExprKind::Abstraction {
+                param,
+                builtin,
+                body,
+            } => {
+                let mut function = Function::new(Some(Rc::clone(param)), expr.span);
+                function.builtin = builtin.then(|| builtins.get(param)).flatten();
+                self.scopes.push(Scope {
+                    function,
+                    captured: Vec::new(),
+                    locals: 0,
+                });
+                self.compile(body, true, builtins);
+                self.emit(Op::End, body.span);
+                let index = self.finish_function();
+                self.emit(Op::Closure(index), expr.span);
+            }
+            ExprKind::Recursion(arg) if tail => {
+                self.compile_expr(arg, builtins);
+                self.emit(Op::Next, arg.span);
+            }
+            ExprKind::Recursion(arg) => {
+                self.scopes.push(Scope {
+                    function: Function::new(None, arg.span),
+                    captured: Vec::new(),
+                    locals: 0,
+                });
+                self.compile_expr(arg, builtins);
+                self.emit(Op::Return, arg.span);
+                let index = self.finish_function();
+                self.emit(Op::Recursion(index), expr.span);
+            }
+            ExprKind::List { elements, rest } => {
+                for element in elements {
+                    self.compile_expr(element, builtins);
+                }
+                if let Some(rest) = rest {
+                    self.compile_expr(rest, builtins);
+                }
+                self.emit(Op::List(elements.len() as u32, rest.is_some()), expr.span);
+            }
+            ExprKind::Let { value, body, .. } => {
+                self.compile_expr(value, builtins);
+                self.emit(Op::Let, value.span);
+                self.scopes.last_mut().expect("a scope").locals += 1;
+                self.compile(body, tail, builtins);
+                self.scopes.last_mut().expect("a scope").locals -= 1;
+                self.emit(Op::EndLet, expr.span);
+            }
+            ExprKind::Application { func, arg } => {
+                self.compile_expr(func, builtins);
+                let func_end = self.last_op();
+                self.compile_expr(arg, builtins);
+                self.emit(Op::Apply(func_end), expr.span);
+            }
+            ExprKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.compile_expr(condition, builtins);
+                let branch = self.current().code.len();
+                self.emit(Op::Branch(0, 0), condition.span);
+                self.compile(then_branch, tail, builtins);
+                let jump = self.current().code.len();
+                self.emit(Op::Jump(0), then_branch.span);
+                let else_start = self.current().code.len() as u32;
+                self.compile(else_branch, tail, builtins);
+                let end = self.current().code.len() as u32;
+                let code = &mut self.current().code;
+                code[branch] = Op::Branch(else_start, end);
+                code[jump] = Op::Jump(end);
+            }
+        }
+    }
+
+    // The parameter `depth` abstractions and `let`s out from the code being
+    // compiled in the function at `level`, captured through every function in
+    // between. Statements and 𝑓 arguments have no parameter, but may have
+    // `let`s.
+    fn capture(&mut self, depth: usize, level: usize) -> Capture {
+        let scope = &self.scopes[level];
+        if depth < scope.locals {
+            return Capture::Local((scope.locals - 1 - depth) as u32);
+        }
+        let depth = depth - scope.locals;
+        let has_param = scope.function.param.is_some();
+        if has_param && depth == 0 {
+            return Capture::Param;
+        }
+        if let Some(index) = scope
+            .captured
+            .iter()
+            .position(|captured| *captured == depth)
+        {
+            return Capture::Captured(index as u32);
+        }
+        let outer_depth = if has_param { depth - 1 } else { depth };
+        let outer = self.capture(outer_depth, level - 1);
+        let scope = &mut self.scopes[level];
+        scope.captured.push(depth);
+        scope.function.captures.push(outer);
+        Capture::Captured(scope.function.captures.len() as u32 - 1)
+    }
+
+    // Moves the innermost function into its parent, returning its index there.
+    fn finish_function(&mut self) -> u32 {
+        let scope = self.scopes.pop().expect("function scope");
+        let parent = &mut self.current().functions;
+        parent.push(Rc::new(scope.function));
+        parent.len() as u32 - 1
+    }
+
+    fn current(&mut self) -> &mut Function {
+        &mut self.scopes.last_mut().expect("a scope").function
+    }
+
+    fn emit(&mut self, op: Op, span: Span) {
+        let function = self.current();
+        function.code.push(op);
+        function.spans.push(span);
+    }
+
+    fn last_op(&mut self) -> u32 {
+        self.current().code.len() as u32 - 1
+    }
+}
@@ -0,0 +1,303 @@
+// Static checks behind `lamda check`: finds problems in a parsed program
+// without evaluating anything, so no built-in ever runs.
+//
+// Besides unbound names, which the resolver reports, it looks for
+// * applications of a number, `(5) 3` evaluates to 5 and ignores the argument,
+// * built-ins applied to a number they reject, e.g. `(λ@ascii. ascii) 300`,
+// * built-ins that can never run because their body always evaluates to HALT.
+
+use super::ast::{Binding, Expr, ExprKind, Program, Statement};
+use super::builtins::BuiltinRegistry;
+use super::error::{Diagnostic, Severity};
+use super::resolver::Resolver;
+
+use std::collections::{HashMap, HashSet};
+
+// Every problem in `program`, in source order. Errors would fail at run time,
+// warnings point at code that runs but can't be doing what was meant.
+pub fn check_program(program: &mut Program, builtins: &BuiltinRegistry) -> Vec<Diagnostic> {
+    let mut resolver = Resolver::new();
+    let mut diagnostics = resolver.resolve_program(program, builtins);
+
+    let mut checker = Checker {
+        builtins,
+        diagnostics: Vec::new(),
+        literals: HashSet::new(),
+        halts: HashSet::new(),
+    };
+    // Only globals bound once are known to keep their value.
+    let mut binding_count = HashMap::<&str, usize>::new();
+    for statement in &program.statements {
+        if let Statement::Binding { name, .. } = statement {
+            *binding_count.entry(name).or_default() += 1;
+        }
+    }
+    for statement in &program.statements {
+        let expr = match statement {
+            Statement::Binding { value, .. } | Statement::ExpressionStmt(value) => value,
+            Statement::Comment { .. } | Statement::Eof => continue,
+        };
+        checker.visit(expr, &mut Vec::new());
+        if let Statement::Binding { name, value } = statement
+            && binding_count[name.as_str()] == 1
+            && let Some(slot) = resolver.slot(name)
+        {
+            if let ExprKind::Literal(_) = value.kind {
+                checker.literals.insert(slot);
+            }
+            if checker.halts(value, &mut Vec::new()) {
+                checker.halts.insert(slot);
+            }
+        }
+    }
+
+    diagnostics.append(&mut checker.diagnostics);
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    diagnostics
+}
+
+// Whether any of `diagnostics` is an error rather than a warning.
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error)
+}
+
+struct Checker<'a> {
+    builtins: &'a BuiltinRegistry,
+    diagnostics: Vec<Diagnostic>,
+    // Global slots bound to a number literal.
+    literals: HashSet<usize>,
+    // Global slots bound to something that always evaluates to HALT.
+    halts: HashSet<usize>,
+}
+
+impl Checker<'_> {
+    // `params` says, for every enclosing abstraction and `let`, innermost last,
+    // whether its parameter or name is known to be HALT.
+    fn visit(&mut self, expr: &Expr, params: &mut Vec<bool>) {
+        match &expr.kind {
+            ExprKind::Identifier { .. } | ExprKind::Literal(_) | ExprKind::String(_) => (),
+            ExprKind::Abstraction { body, .. } => {
+                params.push(false);
+                self.visit(body, params);
+                params.pop();
+            }
+            ExprKind::Recursion(arg) => self.visit(arg, params),
+            ExprKind::Application { func, arg } => {
+                self.check_application(expr, func, arg, params);
+                self.visit(arg, params);
+                if let ExprKind::Abstraction { body, .. } = &func.kind {
+                    let halts = self.halts(arg, params);
+                    params.push(halts);
+                    self.visit(body, params);
+                    params.pop();
+                } else {
+                    self.visit(func, params);
+                }
+            }
+            ExprKind::BinaryOperation { lhs, rhs, .. } => {
+                self.visit(lhs, params);
+                self.visit(rhs, params);
+            }
+            ExprKind::UnaryOperation { operand, .. } => self.visit(operand, params),
+            ExprKind::List { elements, rest } => {
+                for element in elements.iter().chain(rest.as_deref()) {
+                    self.visit(element, params);
+                }
+            }
+            ExprKind::Let { value, body, .. } => {
+                self.visit(value, params);
+                let halts = self.halts(value, params);
+                params.push(halts);
+                self.visit(body, params);
+                params.pop();
+            }
+            ExprKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.visit(condition, params);
+                self.visit(then_branch, params);
+                self.visit(else_branch, params);
+            }
+        }
+    }
+
+    fn check_application(&mut self, expr: &Expr, func: &Expr, arg: &Expr, params: &[bool]) {
+        let applies_literal = match &func.kind {
+            ExprKind::Literal(_) => true,
+            ExprKind::Identifier {
+                binding: Binding::Global { index },
+                ..
+            } => self.literals.contains(index),
+            _ => false,
+        };
+        if applies_literal {
+            self.diagnostics.push(
+                Diagnostic::warning("a number is applied like an abstraction", func.span)
+                    .with_hint("the application evaluates to the number and ignores its argument"),
+            );
+            return;
+        }
+
+        let ExprKind::Abstraction {
+            param,
+            builtin: true,
+            body,
+        } = &func.kind
+        else {
+            return;
+        };
+        // Unknown built-ins are reported by the resolver.
+        let Some(builtin) = self.builtins.get(param) else {
+            return;
+        };
+        // A body that is 𝑓 still hands its argument to the built-in.
+        let mut params = params.to_vec();
+        params.push(self.halts(arg, &mut params.clone()));
+        if !matches!(body.kind, ExprKind::Recursion(_)) && self.halts(body, &mut params) {
+            self.diagnostics.push(
+                Diagnostic::warning(format!("λ@{} never runs", param), func.span)
+                    .with_hint("its body always evaluates to HALT, which skips the built-in"),
+            );
+            return;
+        }
+        if let Some(number) = constant(body, constant(arg, None))
+            && builtin.argument.check(Some(number)).is_none()
+        {
+            self.diagnostics.push(
+                Diagnostic::new(
+                    format!("λ@{} only takes {}", param, builtin.expects),
+                    expr.span,
+                )
+                .with_found(number),
+            );
+        }
+    }
+
+    // Whether `expr` evaluates to HALT whenever it finishes.
+    fn halts(&self, expr: &Expr, params: &mut Vec<bool>) -> bool {
+        match &expr.kind {
+            ExprKind::Identifier {
+                binding: Binding::Local { depth },
+                ..
+            } => params.iter().rev().nth(*depth).copied().unwrap_or(false),
+            ExprKind::Identifier {
+                binding: Binding::Global { index },
+                ..
+            } => self.halts.contains(index),
+            ExprKind::Application { func, arg } => match &func.kind {
+                // 𝑓 only stops looping with HALT.
+                ExprKind::Abstraction { body, .. }
+                    if matches!(body.kind, ExprKind::Recursion(_)) =>
+                {
+                    true
+                }
+                ExprKind::Abstraction { body, .. } => {
+                    let halts = self.halts(arg, params);
+                    params.push(halts);
+                    let body_halts = self.halts(body, params);
+                    params.pop();
+                    body_halts
+                }
+                _ => self.halts(func, params),
+            },
+            ExprKind::If {
+                then_branch,
+                else_branch,
+                ..
+            } => self.halts(then_branch, params) && self.halts(else_branch, params),
+            ExprKind::Let { value, body, .. } => {
+                let halts = self.halts(value, params);
+                params.push(halts);
+                let body_halts = self.halts(body, params);
+                params.pop();
+                body_halts
+            }
+            _ => false,
+        }
+    }
+}
+
+// The number `expr` evaluates to if that's known without running anything,
+// `param` being the value of the innermost abstraction's parameter.
+fn constant(expr: &Expr, param: Option<f64>) -> Option<f64> {
+    match &expr.kind {
+        ExprKind::Literal(number) => Some(*number),
+        ExprKind::Identifier {
+            binding: Binding::Local { depth: 0 },
+            ..
+        } => param,
+        ExprKind::BinaryOperation { op, lhs, rhs } => {
+            Some(op.apply(constant(lhs, param)?, constant(rhs, param)?))
+        }
+        ExprKind::UnaryOperation { op, operand } => Some(op.apply(constant(operand, param)?)),
+        ExprKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => match constant(condition, param)? {
+            0. => constant(else_branch, param),
+            _ => constant(then_branch, param),
+        },
+        // The name hides the parameter in the body.
+        ExprKind::Let { value, body, .. } => constant(body, constant(value, param)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Parser, lexer::Lexer};
+
+    fn messages(source: &str) -> Vec<(Severity, String)> {
+        let mut program = Parser::parse_program(Lexer::new(source).get_tokens().unwrap()).unwrap();
+        check_program(&mut program, &BuiltinRegistry::standard())
+            .into_iter()
+            .map(|diagnostic| (diagnostic.severity, diagnostic.message.clone()))
+            .collect()
+    }
+
+    const CHAR_ERROR: &str = "λ@char only takes Unicode code points in decimal form, \
+                              from 0 to 1114111 except the surrogates 55296 to 57343.";
+
+    #[test]
+    fn reports_every_problem_without_running() {
+        // λ@input would block if anything ran.
+        let source = "(λ@input. input) 1\n(λ@ascii. ascii) 300\n(λ@print. y) 0\n(5) 3\n\
+                      halt = (λn. 𝑓(n-1)) 3\n(λ@print. print) halt\n\
+                      (λ@ascii. 255) 0 (λ@char. 55296) 0 (λ@char. 8.5) 0";
+        assert_eq!(
+            messages(source),
+            [
+                (
+                    Severity::Error,
+                    "λ@ascii only takes ASCII values in decimal form, ranging from 0 to 255."
+                        .to_string()
+                ),
+                (Severity::Error, "unbound binding 'y'".to_string()),
+                (
+                    Severity::Warning,
+                    "a number is applied like an abstraction".to_string()
+                ),
+                (Severity::Warning, "λ@print never runs".to_string()),
+                (Severity::Error, CHAR_ERROR.to_string()),
+                (Severity::Error, CHAR_ERROR.to_string()),
+            ]
+        );
+        assert!(messages(include_str!("../code.lamda")).is_empty());
+    }
+
+    #[test]
+    fn checks_only_the_built_ins_opted_into() {
+        let source = "(λlength. length + 1) 2\nhead = λtail. tail\n(λ@print. (head) 3) 0\n\
+                      (λ@length. 3) 0";
+        assert_eq!(
+            messages(source),
+            [(Severity::Error, "λ@length only takes a list.".to_string())]
+        );
+    }
+}
@@ -0,0 +1,218 @@
+// The `lamda` command line. Argument handling is kept dependency free on
+// purpose, src/main.rs only calls `main`.
+
+use super::ast::Parser;
+use super::builtins::BuiltinRegistry;
+use super::check;
+use super::format::{self, FormatConfig};
+use super::interpreter::{Interpreter, InterpreterConfig};
+use super::lexer::Lexer;
+use super::vm::Vm;
+
+use std::{env, fs, process::ExitCode, thread, time::Duration};
+
+pub const USAGE: &str = "\
+Usage: lamda [options] [file]
+       lamda check <file>
+       lamda fmt [--check] [--unicode] <file>
+
+Without a file an interactive REPL is started. `check` reports problems in
+<file> without running it. `fmt` rewrites <file> in the canonical layout:
+--check only fails if <file> would change, --unicode writes \\x., lambda x.
+and rec(...) as λx. and 𝑓(...).
+
+Options:
+  --max-steps <n>    stop after <n> evaluation steps
+  --max-depth <n>    stop when applications nest deeper than <n>
+  --timeout <ms>     stop after <ms> milliseconds
+  --vm               run on the bytecode VM instead of the tree-walking interpreter
+  -h, --help         show this message";
+
+#[derive(Debug, Default, PartialEq)]
+pub enum Command {
+    #[default]
+    Run,
+    Check,
+    Fmt {
+        check: bool,
+        config: FormatConfig,
+    },
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub command: Command,
+    pub source_path: Option<String>,
+    pub config: InterpreterConfig,
+    pub vm: bool,
+    pub help: bool,
+}
+
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--max-steps" => options.config.max_steps = Some(value(&arg, args.next())?),
+            "--max-depth" => options.config.max_depth = Some(value(&arg, args.next())?),
+            "--timeout" => {
+                options.config.timeout = Some(Duration::from_millis(value(&arg, args.next())?))
+            }
+            "--vm" => options.vm = true,
+            "--check" => match &mut options.command {
+                Command::Fmt { check, .. } => *check = true,
+                _ => return Err("--check only applies to fmt".to_string()),
+            },
+            "--unicode" => match &mut options.command {
+                Command::Fmt { config, .. } => config.unicode = true,
+                _ => return Err("--unicode only applies to fmt".to_string()),
+            },
+            "-h" | "--help" => options.help = true,
+            flag if flag.starts_with('-') => return Err(format!("Unknown option {}", flag)),
+            // Only as the first argument, `lamda check` on its own would
+            // otherwise be a file named check. The same goes for fmt.
+            "check" if options.source_path.is_none() && options.command == Command::Run => {
+                options.command = Command::Check
+            }
+            "fmt" if options.source_path.is_none() && options.command == Command::Run => {
+                options.command = Command::Fmt {
+                    check: false,
+                    config: FormatConfig::default(),
+                }
+            }
+            _ if options.source_path.is_some() => {
+                return Err(format!("Unexpected argument {}", arg));
+            }
+            _ => options.source_path = Some(arg),
+        }
+    }
+    match options.command {
+        Command::Check if options.source_path.is_none() => {
+            return Err("check needs a file".to_string());
+        }
+        Command::Fmt { .. } if options.source_path.is_none() => {
+            return Err("fmt needs a file".to_string());
+        }
+        _ => (),
+    }
+    Ok(options)
+}
+
+fn value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let Some(value) = value else {
+        return Err(format!("{} needs a value", flag));
+    };
+    value
+        .parse()
+        .map_err(|_| format!("{} expects a whole number, got {}", flag, value))
+}
+
+// The tree-walking interpreter recurses once per nested application, so
+// everything runs on a thread with room for a few hundred thousand of them.
+// Only the pages actually touched get committed.
+const EVALUATION_STACK: usize = 512 << 20;
+
+// Left for the frames outside `evaluate_program` and between two stack checks.
+const STACK_MARGIN: usize = 1 << 20;
+
+pub fn main() -> ExitCode {
+    let evaluation = thread::Builder::new()
+        .stack_size(EVALUATION_STACK)
+        .spawn(run)
+        .expect("Unable to start the evaluation thread");
+    // A panic has already been reported by the panic hook.
+    evaluation.join().unwrap_or(ExitCode::FAILURE)
+}
+
+fn run() -> ExitCode {
+    let mut options = match parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    if options.help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    options.config.max_stack = Some(EVALUATION_STACK - STACK_MARGIN);
+    let Some(source_path) = options.source_path else {
+        return crate::repl::run(options.config);
+    };
+    let source = match fs::read_to_string(&source_path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Unable to read {}: {}", source_path, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let tokens = match Lexer::new(&source).get_tokens() {
+        Ok(tokens) => tokens,
+        Err(diagnostic) => {
+            eprint!("{}", diagnostic.with_file(&source_path).render(&source));
+            return ExitCode::FAILURE;
+        }
+    };
+    let (mut ast, diagnostics) = Parser::parse_recovering(tokens);
+    if !diagnostics.is_empty() {
+        for diagnostic in diagnostics {
+            eprint!("{}", diagnostic.with_file(&source_path).render(&source));
+        }
+        return ExitCode::FAILURE;
+    }
+
+    if options.command == Command::Check {
+        let diagnostics = check::check_program(&mut ast, &BuiltinRegistry::standard());
+        for diagnostic in &diagnostics {
+            eprint!(
+                "{}",
+                diagnostic.clone().with_file(&source_path).render(&source)
+            );
+        }
+        return if check::has_errors(&diagnostics) {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        };
+    }
+
+    if let Command::Fmt { check, config } = &options.command {
+        let formatted = format::format_program(&ast, &source, config);
+        if formatted == source {
+            return ExitCode::SUCCESS;
+        }
+        if *check {
+            eprintln!(
+                "{} is not formatted, run lamda fmt {}",
+                source_path, source_path
+            );
+            return ExitCode::FAILURE;
+        }
+        if let Err(e) = fs::write(&source_path, formatted) {
+            eprintln!("Unable to write {}: {}", source_path, e);
+            return ExitCode::FAILURE;
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    let (result, line_open) = if options.vm {
+        let mut vm = Vm::with_config(options.config);
+        let result = vm.evaluate_program(&mut ast).map(drop);
+        (result, vm.host_mut().take_line_open())
+    } else {
+        let mut interpreter = Interpreter::with_config(options.config);
+        let result = interpreter.evaluate_program(&mut ast).map(drop);
+        (result, interpreter.host_mut().take_line_open())
+    };
+
+    if let Err(diagnostic) = result {
+        // Keep the error off the end of whatever the program printed last.
+        if line_open {
+            println!();
+        }
+        eprint!("{}", diagnostic.with_file(&source_path).render(&source));
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
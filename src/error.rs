@@ -1,13 +1,365 @@
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    rc::Rc,
+};
+
+// Byte range into the source text, `end` is exclusive.
+// `line` and `col` are the 1-based position of `start`, with columns counted in
+// characters rather than bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+    // Which of the REPL's inputs the offsets are into, see `Lexer::with_input`.
+    // Always 0 for a single source.
+    pub input: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, col: usize) -> Self {
+        Self {
+            start,
+            end,
+            line,
+            col,
+            input: 0,
+        }
+    }
+}
+
+impl Span {
+    // Smallest span covering both, positioned at whichever starts first.
+    pub fn to(self, other: Span) -> Self {
+        let first = if other.start < self.start {
+            other
+        } else {
+            self
+        };
+        Self {
+            start: first.start,
+            end: self.end.max(other.end),
+            ..first
+        }
+    }
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Self::new(0, 0, 1, 1)
+    }
+}
+
+// A problem in the user's program, reported with enough context to be rendered
+// as a caret-underlined excerpt of the source.
+// Boxed, so `Result<_, Diagnostic>` stays a pointer wide on the happy path.
+#[derive(Debug, Clone)]
+pub struct Diagnostic(Box<DiagnosticDetails>);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    // Only `lamda check` reports these, the program still runs as written.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DiagnosticDetails {
+    pub severity: Severity,
+    pub message: String,
+    pub file: Option<String>,
+    pub span: Span,
+    pub expected: Option<String>,
+    pub found: Option<String>,
+    pub hint: Option<String>,
+    // Applications that were active when a runtime error happened, outermost first.
+    pub trace: Vec<TraceFrame>,
+}
+
+// One active application, e.g. `(λ@print. 𝑓(print-1)) 10` while it recurses.
+#[derive(Debug, Clone)]
+pub struct TraceFrame {
+    pub name: Rc<str>,
+    // Written `λ@name.`.
+    pub builtin: bool,
+    pub span: Span,
+    // How many times 𝑓 has re-applied this abstraction so far.
+    pub recursion_depth: usize,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Self(Box::new(DiagnosticDetails {
+            severity: Severity::Error,
+            message: message.into(),
+            file: None,
+            span,
+            expected: None,
+            found: None,
+            hint: None,
+            trace: Vec::new(),
+        }))
+    }
+
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        let mut diagnostic = Self::new(message, span);
+        diagnostic.severity = Severity::Warning;
+        diagnostic
+    }
+
+    pub fn with_file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    pub fn with_expected(mut self, expected: impl fmt::Display) -> Self {
+        self.expected = Some(expected.to_string());
+        self
+    }
+
+    pub fn with_found(mut self, found: impl fmt::Display) -> Self {
+        self.found = Some(found.to_string());
+        self
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    pub fn with_trace(mut self, trace: Vec<TraceFrame>) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    // error: unexpected token
+    //  --> main.lamda:1:5
+    //   |
+    // 1 | (λx x) 10
+    //   |     ^ expected '.', found identifier 'x'
+    //   = hint: ...
+    pub fn render(&self, source: &str) -> String {
+        let Span { line: row, col, .. } = self.span;
+        let gutter = " ".repeat(row.to_string().len());
+        let mut rendered = format!("{}: {}\n", self.severity, self.message);
+        rendered.push_str(&format!(
+            "{}--> {}:{}:{}\n",
+            gutter,
+            self.file.as_deref().unwrap_or("<input>"),
+            row,
+            col
+        ));
+        let note = match (&self.expected, &self.found) {
+            (Some(expected), Some(found)) => format!("expected {}, found {}", expected, found),
+            (Some(expected), None) => format!("expected {}", expected),
+            (None, Some(found)) => format!("found {}", found),
+            (None, None) => String::new(),
+        };
+
+        // A span from some other source doesn't point into this one, the line
+        // and column above are all there is to go by then.
+        if self.span.start > source.len() {
+            if !note.is_empty() {
+                rendered.push_str(&format!("{} = {}\n", gutter, note));
+            }
+        } else {
+            let start = floor_char_boundary(source, self.span.start);
+            let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = source[start..]
+                .find('\n')
+                .map_or(source.len(), |i| start + i);
+            let line = &source[line_start..line_end];
+            let line = line.strip_suffix('\r').unwrap_or(line);
+
+            // Tabs are kept in the padding so the carets line up however wide
+            // the terminal draws them.
+            let padding = source[line_start..start]
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+            let span_end =
+                floor_char_boundary(source, self.span.end.clamp(start, line_start + line.len()));
+            let underline_width = source[start..span_end].chars().count().max(1);
+
+            rendered.push_str(&format!("{} |\n", gutter));
+            rendered.push_str(&format!("{} | {}\n", row, line));
+            rendered.push_str(&format!(
+                "{} | {}{}",
+                gutter,
+                padding,
+                "^".repeat(underline_width)
+            ));
+            if !note.is_empty() {
+                rendered.push_str(&format!(" {}", note));
+            }
+            rendered.push('\n');
+        }
+        if let Some(hint) = &self.hint {
+            rendered.push_str(&format!("{} = hint: {}\n", gutter, hint));
+        }
+        if !self.trace.is_empty() {
+            rendered.push_str(&format!("{} = trace, innermost last:\n", gutter));
+            // Plain recursion repeats the same frame, print it once with a count.
+            let mut frames = self.trace.iter().peekable();
+            while let Some(frame) = frames.next() {
+                let mut repeated = 1;
+                while frames
+                    .next_if(|next| {
+                        next.name == frame.name
+                            && next.span == frame.span
+                            && next.recursion_depth == frame.recursion_depth
+                    })
+                    .is_some()
+                {
+                    repeated += 1;
+                }
+                rendered.push_str(&format!(
+                    "{}     λ{}{} applied at {}:{}",
+                    gutter,
+                    if frame.builtin { "@" } else { "" },
+                    frame.name,
+                    frame.span.line,
+                    frame.span.col
+                ));
+                if frame.recursion_depth > 0 {
+                    rendered.push_str(&format!(", 𝑓 depth {}", frame.recursion_depth));
+                }
+                if repeated > 1 {
+                    rendered.push_str(&format!(" ({} times)", repeated));
+                }
+                rendered.push('\n');
+            }
+        }
+        rendered
+    }
+}
+
+// The closest offset at or before `index` that starts a character.
+fn floor_char_boundary(source: &str, index: usize) -> usize {
+    (0..=index.min(source.len()))
+        .rev()
+        .find(|&index| source.is_char_boundary(index))
+        .unwrap_or_default()
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(expected) = &self.expected {
+            write!(f, ", expected {}", expected)?;
+        }
+        if let Some(found) = &self.found {
+            write!(f, ", found {}", found)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+impl Deref for Diagnostic {
+    type Target = DiagnosticDetails;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Diagnostic {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
 #[macro_export]
 macro_rules! throw_lexer_syntax_error {
-    ($expected:expr, $got:expr, $row:expr, $col:expr) => {
-        panic!("Expected: '{}', got: '{}' at {}:{}", $expected, $got, $row, $col)
+    ($expected:expr, $got:expr, $span:expr) => {
+        return Err($crate::error::Diagnostic::new("invalid token", $span)
+            .with_expected($expected)
+            .with_found($got))
     };
 }
 
 #[macro_export]
 macro_rules! throw_syntax_error {
-    ($expected:expr, $got:expr) => {
-        panic!("Unexpected token: {:?}, expected: {:?}", $expected, $got)
+    ($expected:expr, $got:expr, $span:expr) => {
+        return Err($crate::error::Diagnostic::new("unexpected token", $span)
+            .with_expected($expected)
+            .with_found($got))
+    };
+    ($expected:expr, $got:expr, $span:expr, $hint:expr) => {
+        return Err($crate::error::Diagnostic::new("unexpected token", $span)
+            .with_expected($expected)
+            .with_found($got)
+            .with_hint($hint))
     };
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_an_excerpt_with_a_caret() {
+        let diagnostic = Diagnostic::new("unexpected token", Span::new(5, 6, 1, 5))
+            .with_file("main.lamda")
+            .with_expected("'.'")
+            .with_found("identifier 'x'")
+            .with_hint("an abstraction is written λparam. body");
+        assert_eq!(
+            diagnostic.render("(λx x) 10\n2"),
+            "error: unexpected token\n \
+             --> main.lamda:1:5\n  \
+             |\n\
+             1 | (λx x) 10\n  \
+             |     ^ expected '.', found identifier 'x'\n  \
+             = hint: an abstraction is written λparam. body\n"
+        );
+
+        let diagnostic = Diagnostic::new("malformed number", Span::new(0, 2, 1, 1))
+            .with_expected("digits after the exponent");
+        assert_eq!(
+            diagnostic.render("1e"),
+            "error: malformed number\n --> <input>:1:1\n  |\n1 | 1e\n  | ^^ expected digits after the exponent\n"
+        );
+    }
+
+    #[test]
+    fn keeps_tabs_and_drops_carriage_returns() {
+        // The span runs into the line break, the carets stop at the line's end.
+        let source = "a = 1\r\n\tb + \"x\"\r\n";
+        let diagnostic = Diagnostic::warning("strings can't be added", Span::new(12, 17, 2, 6))
+            .with_found("string \"x\"");
+        assert_eq!(
+            diagnostic.render(source),
+            "warning: strings can't be added\n --> <input>:2:6\n  |\n\
+             2 | \tb + \"x\"\n  | \t    ^^^ found string \"x\"\n"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_position_outside_the_source() {
+        // Offsets into some other source, one inside a 'λ' here and one past
+        // the end.
+        let diagnostic = Diagnostic::new("bad operand", Span::new(5, 13, 1, 6)).with_found("3");
+        assert_eq!(
+            diagnostic.render("x = λλλ"),
+            "error: bad operand\n --> <input>:1:6\n  |\n1 | x = λλλ\n  |     ^^^ found 3\n"
+        );
+        assert_eq!(
+            diagnostic.render("x"),
+            "error: bad operand\n --> <input>:1:6\n  = found 3\n"
+        );
+    }
+}
@@ -0,0 +1,378 @@
+// Prints a parsed program back as source, behind `lamda fmt`.
+//
+// Inside a statement everything is respaced: `λx. body`, `𝑓(arg)`, `(func) arg`
+// and one space around operators, with only the grouping parentheses the tree
+// needs to read back the same. Numbers are kept as written and so is the
+// spelling of λ and 𝑓, unless FormatConfig asks for the Unicode ones. Between
+// statements the layout that says something is kept: statements written on one
+// line stay on one line, a comment after a statement stays after it, and blank
+// lines between statements become a single one.
+
+use super::ast::{BinaryOp, Expr, ExprKind, Precedence, Program, Statement};
+use super::error::Span;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FormatConfig {
+    // Writes `\x.` and `lambda x.` as `λx.` and `rec(...)` as `𝑓(...)`.
+    pub unicode: bool,
+}
+
+pub fn format_program(program: &Program, source: &str, config: &FormatConfig) -> String {
+    let formatter = Formatter { source, config };
+    let mut formatted = String::new();
+    // Where the previous statement ended.
+    let mut last_line = None;
+    for statement in &program.statements {
+        let (text, span) = match statement {
+            Statement::Binding { name, value } => {
+                (format!("{} = {}", name, formatter.expr(value)), value.span)
+            }
+            Statement::ExpressionStmt(expr) => (formatter.expr(expr), expr.span),
+            Statement::Comment { text, span } => (format!("//{}", text), *span),
+            Statement::Eof => continue,
+        };
+        match last_line {
+            Some(line) if span.line == line => formatted.push(' '),
+            Some(line) => {
+                formatted.push('\n');
+                if span.line > line + 1 {
+                    formatted.push('\n');
+                }
+            }
+            None => (),
+        }
+        formatted.push_str(&text);
+        last_line = Some(end_line(source, span));
+    }
+    if !formatted.is_empty() {
+        formatted.push('\n');
+    }
+    formatted
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    config: &'a FormatConfig,
+}
+
+impl Formatter<'_> {
+    fn expr(&self, expr: &Expr) -> String {
+        let mut formatted = String::new();
+        self.write_expr(&mut formatted, expr);
+        formatted
+    }
+
+    fn write_expr(&self, out: &mut String, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Identifier { name, .. } => out.push_str(name),
+            ExprKind::Literal(number) => match self.written(expr.span) {
+                written if !written.is_empty() => out.push_str(written),
+                _ => out.push_str(&number.to_string()),
+            },
+            // Escapes are kept as written too.
+            ExprKind::String(text) => match self.written(expr.span) {
+                written if !written.is_empty() => out.push_str(written),
+                _ => out.push_str(&format!("{:?}", text)),
+            },
+            ExprKind::Abstraction {
+                param,
+                builtin,
+                body,
+            } => {
+                let lamda = match self.written(expr.span) {
+                    _ if self.config.unicode => "λ",
+                    written if written.starts_with('\\') => "\\",
+                    written if written.starts_with("lambda") => "lambda ",
+                    _ => "λ",
+                };
+                out.push_str(&format!("{}{}", lamda, parameter(param, *builtin)));
+                // `λx y. body` stays that way.
+                let mut body = body;
+                while let ExprKind::Abstraction {
+                    param,
+                    builtin,
+                    body: inner,
+                } = &body.kind
+                    && self.is_sugar(body)
+                {
+                    out.push_str(&format!(" {}", parameter(param, *builtin)));
+                    body = inner;
+                }
+                out.push_str(". ");
+                self.write_expr(out, body);
+            }
+            ExprKind::Recursion(arg) => {
+                let recursion = match self.written(expr.span) {
+                    written if written.starts_with("rec") && !self.config.unicode => "rec",
+                    _ => "𝑓",
+                };
+                out.push_str(&format!("{}(", recursion));
+                self.write_expr(out, arg);
+                out.push(')');
+            }
+            // The parentheses around the function are the application itself,
+            // the argument runs to the end of the expression anyway. `(f) 2 10`
+            // stays that way.
+            ExprKind::Application { func, arg } => {
+                if matches!(func.kind, ExprKind::Application { .. }) && self.is_sugar(expr) {
+                    self.write_expr(out, func);
+                } else {
+                    out.push('(');
+                    self.write_expr(out, func);
+                    out.push(')');
+                }
+                out.push(' ');
+                self.write_expr(out, arg);
+            }
+            // The brackets end every element, none of them needs grouping.
+            ExprKind::List { elements, rest } => {
+                out.push('[');
+                for (index, element) in elements.iter().enumerate() {
+                    if index > 0 {
+                        out.push_str(", ");
+                    }
+                    self.write_expr(out, element);
+                }
+                if let Some(rest) = rest {
+                    if !elements.is_empty() {
+                        out.push_str(", ");
+                    }
+                    out.push_str("..");
+                    self.write_expr(out, rest);
+                }
+                out.push(']');
+            }
+            // `then` and `else` end the condition and the first branch whatever
+            // they hold, only the last branch is open-ended.
+            ExprKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                out.push_str("if ");
+                self.write_expr(out, condition);
+                out.push_str(" then ");
+                self.write_expr(out, then_branch);
+                out.push_str(" else ");
+                self.write_expr(out, else_branch);
+            }
+            // `in` ends the value, the body is open-ended.
+            ExprKind::Let { name, value, body } => {
+                out.push_str(&format!("let {} = ", name));
+                self.write_expr(out, value);
+                out.push_str(" in ");
+                self.write_expr(out, body);
+            }
+            ExprKind::BinaryOperation { op, lhs, rhs } => {
+                let group_lhs = open_ended(lhs) || binds_looser(lhs, *op, false);
+                self.write_grouped(out, lhs, group_lhs);
+                out.push_str(&format!(" {} ", op));
+                self.write_grouped(out, rhs, binds_looser(rhs, *op, true));
+            }
+            ExprKind::UnaryOperation { op, operand } => {
+                out.push_str(&op.to_string());
+                let group = precedence(operand).is_some_and(|inner| inner < Precedence::Prefix);
+                self.write_grouped(out, operand, group);
+            }
+        }
+    }
+
+    fn write_grouped(&self, out: &mut String, expr: &Expr, group: bool) {
+        if group {
+            out.push('(');
+            self.write_expr(out, expr);
+            out.push(')');
+        } else {
+            self.write_expr(out, expr);
+        }
+    }
+
+    // Whether `expr` was written as part of the sugar around it: an inner
+    // abstraction of `λx y. body`, which starts at its parameter, or a
+    // multi-argument application `(f) 2 10`, which starts where the
+    // application of its function does.
+    fn is_sugar(&self, expr: &Expr) -> bool {
+        let written = self.written(expr.span);
+        match &expr.kind {
+            ExprKind::Abstraction { .. } => {
+                !written.is_empty()
+                    && !["λ", "\\", "lambda"]
+                        .iter()
+                        .any(|lamda| written.starts_with(lamda))
+            }
+            ExprKind::Application { func, .. } => {
+                !written.is_empty() && func.span.start == expr.span.start
+            }
+            _ => false,
+        }
+    }
+
+    // The source `span` covers, empty for trees that didn't come from it.
+    fn written(&self, span: Span) -> &str {
+        self.source.get(span.start..span.end).unwrap_or_default()
+    }
+}
+
+// A parameter as written after λ, `@name` when it opts into a built-in.
+fn parameter(name: &str, builtin: bool) -> String {
+    if builtin {
+        format!("@{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+// Whether `expr` ends in an abstraction body or an application argument, which
+// would take in whatever is written after it.
+fn open_ended(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Abstraction { .. }
+        | ExprKind::Application { .. }
+        | ExprKind::If { .. }
+        | ExprKind::Let { .. } => true,
+        ExprKind::BinaryOperation { rhs, .. } => open_ended(rhs),
+        ExprKind::UnaryOperation { operand, .. } => open_ended(operand),
+        _ => false,
+    }
+}
+
+// Whether `expr` is an operation that would come apart next to `op` without
+// grouping, `right` being the side of `op` it's on. On the side `op` doesn't
+// group towards, an operation binding as tightly as `op` comes apart too. `!`
+// starts its operation, so on the right it stays together.
+fn binds_looser(expr: &Expr, op: BinaryOp, right: bool) -> bool {
+    let Some(inner) = precedence(expr) else {
+        return false;
+    };
+    if right && inner == Precedence::Prefix {
+        return false;
+    }
+    let groups_right = op == BinaryOp::Pow;
+    inner < op.precedence() || (inner == op.precedence() && right != groups_right)
+}
+
+fn precedence(expr: &Expr) -> Option<Precedence> {
+    match &expr.kind {
+        ExprKind::BinaryOperation { op, .. } => Some(op.precedence()),
+        ExprKind::UnaryOperation { .. } => Some(Precedence::Prefix),
+        _ => None,
+    }
+}
+
+// The line `span` ends on, spans only know the line they start on.
+fn end_line(source: &str, span: Span) -> usize {
+    let text = source.get(span.start..span.end).unwrap_or_default();
+    span.line + text.matches('\n').count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Parser, lexer::Lexer};
+
+    fn parse(source: &str) -> Program {
+        Parser::parse_program(Lexer::new(source).get_tokens().unwrap()).unwrap()
+    }
+
+    // The tree without spans, comments included.
+    fn shape(program: &Program) -> Vec<String> {
+        program
+            .statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::Comment { text, .. } => format!("Comment({:?})", text),
+                statement => format!("{:?}", statement),
+            })
+            .collect()
+    }
+
+    // Formats `source` into `expected`, which has to parse to the same
+    // program and stay as it is when formatted again.
+    fn assert_formats(source: &str, expected: &str) {
+        let formatted = format_program(&parse(source), source, &FormatConfig::default());
+        assert_eq!(formatted, expected);
+        assert_eq!(shape(&parse(&formatted)), shape(&parse(source)));
+        assert_eq!(
+            format_program(&parse(&formatted), &formatted, &FormatConfig::default()),
+            formatted
+        );
+    }
+
+    #[test]
+    fn keeps_comments_and_one_blank_line() {
+        assert_formats(
+            "// squares\nsquare=λx.x*x   // named\n\n\n(square) 2\n// end",
+            "// squares\nsquare = λx. x * x // named\n\n(square) 2\n// end\n",
+        );
+    }
+
+    #[test]
+    fn spaces_abstractions_and_applications() {
+        assert_formats(
+            "(λ@print.print)(square)1e3+2&3\n(λa.(λb.a-b) 1)2 (λn.𝑓( n-1 ))3",
+            "(λ@print. print) (square) 1e3 + 2 & 3\n(λa. (λb. a - b) 1) 2 (λn. 𝑓(n - 1)) 3\n",
+        );
+        assert_formats(
+            "k=λa b.\\c.(f)a b+((f) a) b",
+            "k = λa b. \\c. (f) a b + ((f) a) b\n",
+        );
+    }
+
+    #[test]
+    fn keeps_only_the_parentheses_operators_need() {
+        assert_formats(
+            "x=((a+b))*(c-d)/(e*f)\ny=a-(b+c)&(d|e)",
+            "x = (a + b) * (c - d) / (e * f)\ny = a - (b + c) & (d | e)\n",
+        );
+        assert_formats(
+            "u=2**(3**2)+(2**3)**2\nt=(!a)**2*!(b<<1)%4",
+            "u = 2 ** 3 ** 2 + (2 ** 3) ** 2\nt = (!a) ** 2 * !(b << 1) % 4\n",
+        );
+        assert_formats(
+            "z=((f) 2)+((λv.v) 3-1)*2",
+            "z = ((f) 2) + ((λv. v) 3 - 1) * 2\n",
+        );
+    }
+
+    #[test]
+    fn parenthesises_conditionals_and_let_only_before_an_operator() {
+        assert_formats(
+            "w=if a>1 then(f)2 else if b then 1 else 0\nv=(if a then b else c)+1",
+            "w = if a > 1 then (f) 2 else if b then 1 else 0\nv = (if a then b else c) + 1\n",
+        );
+        assert_formats(
+            "j=(let a=1 in a)+let b=λx.x in(b)2",
+            "j = (let a = 1 in a) + let b = λx. x in (b) 2\n",
+        );
+    }
+
+    #[test]
+    fn writes_strings_and_lists_back() {
+        assert_formats("(λ@write.\"a\\tb\")0", "(λ@write. \"a\\tb\") 0\n");
+        assert_formats(
+            "l=[ 1,(f) 2 ,λx.x ,]\nm=[0,..l]",
+            "l = [1, (f) 2, λx. x]\nm = [0, ..l]\n",
+        );
+    }
+
+    #[test]
+    fn formats_the_example_to_the_same_program() {
+        let code = include_str!("../code.lamda");
+        let formatted = format_program(&parse(code), code, &FormatConfig::default());
+        assert_eq!(shape(&parse(&formatted)), shape(&parse(code)));
+    }
+
+    #[test]
+    fn keeps_or_normalises_the_ascii_spellings() {
+        let source = "(\\@print.rec(print-1)) 3\n(lambda  @ascii.ascii) 10";
+        let unicode = "(λ@print. 𝑓(print - 1)) 3\n(λ@ascii. ascii) 10\n";
+        let keep = format_program(&parse(source), source, &FormatConfig::default());
+        assert_eq!(
+            keep,
+            "(\\@print. rec(print - 1)) 3\n(lambda @ascii. ascii) 10\n"
+        );
+        let config = FormatConfig { unicode: true };
+        assert_eq!(format_program(&parse(source), source, &config), unicode);
+        assert_eq!(shape(&parse(source)), shape(&parse(unicode)));
+    }
+}
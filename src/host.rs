@@ -0,0 +1,367 @@
+// Everything the built-in abstractions need from the outside world goes through
+// a Host, so the interpreter can run inside a terminal, a test or another program.
+
+use anyhow::{Result, bail};
+use crossterm::event::{self, KeyCode};
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    io::{self, BufRead, IsTerminal, Read, Write},
+    time::Duration,
+};
+
+pub trait Host {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()>;
+
+    fn write_number(&mut self, number: f64) -> Result<()> {
+        self.write_bytes(number.to_string().as_bytes())
+    }
+
+    // `None` once the input has run out.
+    fn read_char(&mut self) -> Result<Option<char>>;
+
+    // `None` once the input has run out.
+    fn read_number(&mut self) -> Result<Option<f64>>;
+
+    // Milliseconds since the Unix epoch.
+    fn now(&mut self) -> Result<f64>;
+
+    fn sleep(&mut self, duration: Duration);
+}
+
+// Lets a caller keep the host, e.g. to read a MemoryHost's output afterwards.
+impl<H: Host + ?Sized> Host for &mut H {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        (**self).write_bytes(bytes)
+    }
+
+    fn write_number(&mut self, number: f64) -> Result<()> {
+        (**self).write_number(number)
+    }
+
+    fn read_char(&mut self) -> Result<Option<char>> {
+        (**self).read_char()
+    }
+
+    fn read_number(&mut self) -> Result<Option<f64>> {
+        (**self).read_number()
+    }
+
+    fn now(&mut self) -> Result<f64> {
+        (**self).now()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        (**self).sleep(duration)
+    }
+}
+
+// Talks to the real terminal: stdout for output and raw-mode key events for input.
+// When stdin is a pipe or a file instead, input is read from the stream.
+pub struct TerminalHost {
+    // Set whenever output leaves the cursor in the middle of a line, so the REPL
+    // knows to break the line before echoing a result.
+    line_open: bool,
+    interactive: bool,
+}
+
+impl Default for TerminalHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TerminalHost {
+    pub fn new() -> Self {
+        Self {
+            line_open: false,
+            interactive: io::stdin().is_terminal(),
+        }
+    }
+
+    fn read_byte(input: &mut impl Read) -> Result<Option<u8>> {
+        let mut byte = [0];
+        match input.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    // One UTF-8 encoded character, invalid sequences come out as U+FFFD.
+    fn read_stream_char(stdin: &mut impl BufRead) -> Result<Option<char>> {
+        let Some(first) = Self::read_byte(stdin)? else {
+            return Ok(None);
+        };
+        let width = match first {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => return Ok(Some(char::REPLACEMENT_CHARACTER)),
+        };
+        let mut bytes = vec![first];
+        for _ in 1..width {
+            match stdin.fill_buf()?.first() {
+                Some(byte) if byte & 0xc0 == 0x80 => {
+                    bytes.push(*byte);
+                    stdin.consume(1);
+                }
+                _ => break,
+            }
+        }
+        Ok(Some(
+            std::str::from_utf8(&bytes)
+                .ok()
+                .and_then(|s| s.chars().next())
+                .unwrap_or(char::REPLACEMENT_CHARACTER),
+        ))
+    }
+
+    // Whitespace separated, the separator after the number is consumed with it.
+    fn read_stream_number(stdin: &mut impl BufRead) -> Result<Option<f64>> {
+        let mut number = Vec::new();
+        while let Some(byte) = Self::read_byte(stdin)? {
+            if !byte.is_ascii_whitespace() {
+                number.push(byte);
+            } else if !number.is_empty() {
+                break;
+            }
+        }
+        if number.is_empty() {
+            return Ok(None);
+        }
+        let number = String::from_utf8_lossy(&number);
+        match number.parse::<f64>() {
+            Ok(number) => Ok(Some(number)),
+            Err(_) => bail!("λ@input expected a number, got '{}'", number),
+        }
+    }
+
+    pub fn take_line_open(&mut self) -> bool {
+        std::mem::take(&mut self.line_open)
+    }
+}
+
+// Raw mode for as long as it's alive, so an error reading a key can't leave the
+// terminal in it.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> Result<Self> {
+        crossterm::terminal::enable_raw_mode()?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // Nothing better to do with a failure while unwinding from another one.
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+}
+
+impl Host for TerminalHost {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        let mut stdout = io::stdout();
+        stdout.write_all(bytes)?;
+        stdout.flush()?;
+        if let Some(last) = bytes.last() {
+            self.line_open = *last != b'\n';
+        }
+        Ok(())
+    }
+
+    fn read_char(&mut self) -> Result<Option<char>> {
+        if !self.interactive {
+            return Self::read_stream_char(&mut io::stdin().lock());
+        }
+        let _raw_mode = RawMode::enable()?;
+        let result;
+        loop {
+            if event::poll(Duration::from_millis(10))?
+                && let event::Event::Key(key_event) = event::read()?
+            {
+                match key_event.code {
+                    KeyCode::Char(c) => {
+                        result = c;
+                        break;
+                    }
+                    KeyCode::Enter => {
+                        result = '\n';
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(Some(result))
+    }
+
+    fn read_number(&mut self) -> Result<Option<f64>> {
+        if !self.interactive {
+            return Self::read_stream_number(&mut io::stdin().lock());
+        }
+        let raw_mode = RawMode::enable()?;
+        let mut is_dot_allowed = true;
+        let mut is_e_allowed = false;
+        let mut is_sign_allowed = false;
+        let mut result = String::new();
+        let mut print = false;
+        loop {
+            if event::poll(Duration::from_millis(10))?
+                && let event::Event::Key(key_event) = event::read()?
+            {
+                match key_event.code {
+                    // is_e_allowed along isn't sufficient to determine if e can be added.
+                    KeyCode::Char('e') if is_e_allowed && !result.contains('e') => {
+                        result.push('e');
+                        is_dot_allowed = false;
+                        is_e_allowed = false;
+                        is_sign_allowed = true;
+                        print = true;
+                    }
+                    KeyCode::Char('+') if is_sign_allowed => {
+                        result.push('+');
+                        is_sign_allowed = false;
+                        print = true;
+                    }
+                    KeyCode::Char('-') if is_sign_allowed => {
+                        result.push('-');
+                        is_sign_allowed = false;
+                        print = true;
+                    }
+                    KeyCode::Char('.') if is_dot_allowed => {
+                        result.push('.');
+                        is_dot_allowed = false;
+                        print = true;
+                        // After, e if there is a digit, sign is no longer allowed;
+                        // i.e 10e10;
+                        is_sign_allowed = !is_e_allowed;
+                        is_e_allowed = true
+                    }
+
+                    KeyCode::Char(c) if c.is_ascii_digit() => {
+                        result.push(c);
+                        is_e_allowed = true;
+                        print = true;
+                    }
+                    KeyCode::Enter => {
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            if print {
+                write!(io::stdout(), "{}", result.chars().last().unwrap_or('\r'))?;
+                io::stdout().flush()?;
+                print = false;
+            }
+        }
+        drop(raw_mode);
+        self.line_open = self.line_open || !result.is_empty();
+        Ok(Some(result.parse::<f64>()?))
+    }
+
+    fn now(&mut self) -> Result<f64> {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(stamp) => Ok(stamp.as_millis() as f64),
+            Err(e) => bail!("SystemTimeError difference: {:?}", e.duration()),
+        }
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+// Scripted input and captured output, for tests and for embedding.
+// Time is simulated: the clock starts at 0 and only moves when sleeping.
+#[derive(Debug, Default)]
+pub struct MemoryHost {
+    input: VecDeque<char>,
+    output: Vec<u8>,
+    clock: f64,
+}
+
+impl MemoryHost {
+    pub fn new(input: &str) -> Self {
+        Self {
+            input: input.chars().collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    // Lossy, λ@ascii can write bytes that aren't valid UTF-8 on their own.
+    pub fn output_string(&self) -> String {
+        String::from_utf8_lossy(&self.output).into_owned()
+    }
+}
+
+impl Host for MemoryHost {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.output.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn read_char(&mut self) -> Result<Option<char>> {
+        Ok(self.input.pop_front())
+    }
+
+    // Numbers are separated by whitespace.
+    fn read_number(&mut self) -> Result<Option<f64>> {
+        while self.input.front().is_some_and(|c| c.is_whitespace()) {
+            self.input.pop_front();
+        }
+        let mut number = String::new();
+        while let Some(c) = self.input.front().filter(|c| !c.is_whitespace()) {
+            number.push(*c);
+            self.input.pop_front();
+        }
+        if number.is_empty() {
+            return Ok(None);
+        }
+        // The separator belongs to the number, like pressing Enter in a terminal.
+        self.input.pop_front();
+        Ok(Some(number.parse::<f64>()?))
+    }
+
+    fn now(&mut self) -> Result<f64> {
+        Ok(self.clock)
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        self.clock += duration.as_millis() as f64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn piped_input_is_read_from_the_stream() {
+        // λ, then a byte that can't start a character.
+        let mut input = &b"a\xce\xbb\xff!"[..];
+        let chars = std::iter::from_fn(|| TerminalHost::read_stream_char(&mut input).unwrap())
+            .collect::<String>();
+        assert_eq!(chars, "aλ\u{fffd}!");
+
+        let mut input = "  12\n-0.5e1 x".as_bytes();
+        assert_eq!(
+            TerminalHost::read_stream_number(&mut input).unwrap(),
+            Some(12.)
+        );
+        assert_eq!(
+            TerminalHost::read_stream_number(&mut input).unwrap(),
+            Some(-5.)
+        );
+        assert!(TerminalHost::read_stream_number(&mut input).is_err());
+        // The end of the stream, which λ@input turns into HALT.
+        assert_eq!(TerminalHost::read_stream_number(&mut input).unwrap(), None);
+        assert_eq!(TerminalHost::read_stream_char(&mut input).unwrap(), None);
+    }
+}
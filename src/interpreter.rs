@@ -1,270 +1,1024 @@
-use super::ast::{Expr, Program};
-use super::abstractions;
-use super::ast::{BinaryOp, Statement};
+use super::ast::{BinaryOp, Binding, Statement};
+use super::ast::{Expr, ExprKind, Parser, Program};
+use super::builtins::{Argument, Builtin, BuiltinRegistry};
+use super::error::{Diagnostic, Span, TraceFrame};
+use super::host::{Host, TerminalHost};
+use super::lexer::Lexer;
+use super::list::{Comparable, Element, List, equal};
+use super::resolver::Resolver;
 
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
-use anyhow::{Ok, Result, bail};
+use std::{
+    fmt,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+type Result<T> = std::result::Result<T, Diagnostic>;
 
 #[derive(Debug, Clone)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum EvaluationValue {
     Literal(f64),
+    String(Rc<str>),
+    List(List<EvaluationValue>),
     // basically, a closure
     Closer(Rc<Abstraction>),
     // The unit value, for statements that don't produce a visible result (like bindings).
     Unit,
-    Recursion(Box<Expr>),
+    // The argument of 𝑓 along with the scope it was written in.
+    Recursion(Rc<Expr>, Environment),
 
     // any lamda receiving the signal "HALT" must not execute
-    // eg. (λprint.(λinput. (λif) 0 0))
-    // (λprint.(λinput. 'HALT'))
-    // (λprint.'HALT')
+    // eg. (λ@print.(λ@input. (λif) 0 0))
+    // (λ@print.(λ@input. 'HALT'))
+    // (λ@print.'HALT')
     // 'HALT'
     // HALT can be stored into a variable.
     HALT,
 }
 
-type Environment = Rc<RefCell<Scope>>;
+impl fmt::Display for EvaluationValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvaluationValue::Literal(literal) => write!(f, "{}", literal),
+            EvaluationValue::String(text) => write!(f, "{:?}", text),
+            EvaluationValue::List(list) => write!(f, "{}", list),
+            EvaluationValue::Closer(abstraction) => {
+                let at = if abstraction.builtin.is_some() {
+                    "@"
+                } else {
+                    ""
+                };
+                write!(f, "<λ{}{}>", at, abstraction.param)
+            }
+            EvaluationValue::Unit => write!(f, "()"),
+            EvaluationValue::Recursion(..) => write!(f, "<𝑓>"),
+            EvaluationValue::HALT => write!(f, "HALT"),
+        }
+    }
+}
+
+// Arguments of the enclosing applications, innermost first. Identifiers are
+// resolved to a depth into this before anything runs, see resolver.rs.
+pub type Environment = Option<Rc<Scope>>;
 
 #[derive(Debug, Clone)]
 
 pub struct Abstraction {
-    param: String,
-    body: Box<Expr>,
+    param: Rc<str>,
+    body: Rc<Expr>,
     env: Environment,
+    // Looked up when the abstraction is created.
+    builtin: Option<Rc<Builtin>>,
 }
 
 #[derive(Debug)]
 
 pub struct Scope {
-    bindings: HashMap<String, EvaluationValue>,
-    parent: Option<Environment>,
+    value: EvaluationValue,
+    parent: Environment,
 }
 
 impl Scope {
-    pub fn global() -> Rc<RefCell<Self>> {
-        Rc::new(RefCell::new(Scope {
-            bindings: HashMap::new(),
-            parent: None,
-        }))
+    pub fn inner(parent: Environment, value: EvaluationValue) -> Environment {
+        Some(Rc::new(Scope { value, parent }))
     }
 
-    pub fn inner(parent: Rc<RefCell<Scope>>) -> Rc<RefCell<Self>> {
-        Rc::new(RefCell::new(Scope {
-            bindings: HashMap::new(),
-            parent: Some(parent),
-        }))
+    pub fn get(env: &Environment, depth: usize) -> Option<&EvaluationValue> {
+        let mut scope = env.as_deref()?;
+        for _ in 0..depth {
+            scope = scope.parent.as_deref()?;
+        }
+        Some(&scope.value)
     }
+}
 
-    pub fn set(&mut self, name: String, value: EvaluationValue) {
-        self.bindings.insert(name, value);
-    }
+// Limits for a single `evaluate_program` call, `None` means unlimited.
+// Evaluation stops with a "fuel exhausted" error once any of them is hit.
+#[derive(Debug, Clone, Default)]
+pub struct InterpreterConfig {
+    // Every evaluated expression counts as one step.
+    pub max_steps: Option<u64>,
+    // Applications active at the same time, 𝑓 iterations don't nest so they
+    // don't count towards this.
+    pub max_depth: Option<usize>,
+    // Wall-clock time, checked between steps, so a blocking λ@input or λ@sleep
+    // can overrun it.
+    pub timeout: Option<Duration>,
+    // Bytes of native stack the tree-walking interpreter may use, it recurses
+    // once per nested application. `None` is DEFAULT_MAX_STACK. The VM keeps
+    // its frames on the heap and ignores this, without a `max_depth` it stops
+    // at vm::DEFAULT_MAX_FRAMES instead.
+    pub max_stack: Option<usize>,
+}
 
-    pub fn get(&self, name: &str) -> Option<EvaluationValue> {
-        match self.bindings.get(name) {
-            Some(val) => Some(val.clone()),
-            None => match &self.parent {
-                Some(parent) => parent.borrow().get(name),
-                None => None,
-            },
+// How often the clock is read when a timeout is set.
+const TIMEOUT_CHECK_INTERVAL: u64 = 1024;
+
+// Half of the 2 MiB Rust gives a spawned thread, the rest is left for whatever
+// called `evaluate_program` and for the frames between two checks.
+pub const DEFAULT_MAX_STACK: usize = 1 << 20;
+
+// Address of a local in the caller's frame, the difference between two of them
+// is the stack used in between.
+#[inline(always)]
+fn stack_address() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+impl InterpreterConfig {
+    // The "fuel exhausted" message and hint once `steps` or `deadline` is past
+    // its limit. Shared by both backends.
+    pub(crate) fn exhausted(
+        &self,
+        steps: u64,
+        deadline: Option<Instant>,
+    ) -> Option<(String, &'static str)> {
+        if let Some(max_steps) = self.max_steps
+            && steps > max_steps
+        {
+            return Some((
+                format!("fuel exhausted after {} evaluation steps", max_steps),
+                "the step limit is set with --max-steps",
+            ));
         }
+        if let (Some(deadline), Some(timeout)) = (deadline, self.timeout)
+            && steps.is_multiple_of(TIMEOUT_CHECK_INTERVAL)
+            && Instant::now() >= deadline
+        {
+            return Some((
+                format!("fuel exhausted after {} ms", timeout.as_millis()),
+                "the time limit is set with --timeout",
+            ));
+        }
+        None
     }
 }
 
-pub struct Interpreter {
-    env: Rc<RefCell<Scope>>,
+pub struct Interpreter<H: Host = TerminalHost> {
+    resolver: Resolver,
+    // Indexed by the slots the resolver hands out, `None` until bound.
+    globals: Vec<Option<EvaluationValue>>,
+    // Where built-ins read and write.
+    host: H,
+    builtins: BuiltinRegistry,
+    // Applications currently being evaluated, innermost last. Only used to
+    // explain where a runtime error came from.
+    call_stack: Vec<TraceFrame>,
+    config: InterpreterConfig,
+    steps: u64,
+    deadline: Option<Instant>,
+    // Where the stack was when `evaluate_program` started.
+    stack_base: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Self {
+        Self::with_config(InterpreterConfig::default())
+    }
+
+    // Runs in the terminal with the standard built-ins.
+    pub fn with_config(config: InterpreterConfig) -> Self {
+        Self::with_host(TerminalHost::new(), config).with_builtins(BuiltinRegistry::standard())
+    }
+}
+
+impl<H: Host> Interpreter<H> {
+    // Starts without any built-ins, they are opted into with `with_builtins`.
+    pub fn with_host(host: H, config: InterpreterConfig) -> Self {
         Interpreter {
-            env: Scope::global(),
+            resolver: Resolver::new(),
+            globals: Vec::new(),
+            host,
+            builtins: BuiltinRegistry::new(),
+            call_stack: Vec::new(),
+            config,
+            steps: 0,
+            deadline: None,
+            stack_base: 0,
         }
     }
-    pub fn evaluate_program(&mut self, program: &Program) -> Result<Vec<EvaluationValue>> {
+
+    pub fn with_builtins(mut self, builtins: BuiltinRegistry) -> Self {
+        self.builtins = builtins;
+        self
+    }
+
+    // Registering a built-in affects abstractions created from then on.
+    pub fn builtins_mut(&mut self) -> &mut BuiltinRegistry {
+        &mut self.builtins
+    }
+
+    pub fn config(&self) -> &InterpreterConfig {
+        &self.config
+    }
+
+    pub fn host(&self) -> &H {
+        &self.host
+    }
+
+    pub fn host_mut(&mut self) -> &mut H {
+        &mut self.host
+    }
+
+    // Starts over with no bindings, keeping the host and limits.
+    pub fn reset(&mut self) {
+        self.resolver = Resolver::new();
+        self.globals.clear();
+        self.call_stack.clear();
+    }
+
+    // Global bindings sorted by name, the REPL lists these with `:env`.
+    pub fn global_bindings(&self) -> Vec<(String, EvaluationValue)> {
+        let mut bindings = self
+            .globals
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, value)| {
+                let value = value.clone()?;
+                Some((self.resolver.global_name(slot).to_string(), value))
+            })
+            .collect::<Vec<_>>();
+        bindings.sort_by(|(a, _), (b, _)| a.cmp(b));
+        bindings
+    }
+
+    // Resolves `program` in place, so its identifiers end up annotated for this
+    // evaluator. Unbound names are reported before any statement runs, the
+    // first one becomes the error.
+    pub fn evaluate_program(&mut self, program: &mut Program) -> Result<Vec<EvaluationValue>> {
+        if let Some(unbound) = self
+            .resolver
+            .resolve_program(program, &self.builtins)
+            .into_iter()
+            .next()
+        {
+            return Err(unbound);
+        }
+        self.globals.resize(self.resolver.global_count(), None);
+
         let mut results = Vec::new();
+        self.steps = 0;
+        self.deadline = self.config.timeout.map(|timeout| Instant::now() + timeout);
+        self.stack_base = stack_address();
         for statement in &program.statements {
+            // A failed statement leaves its frames behind, start clean.
+            self.call_stack.clear();
             let result = self.evaluate_statement(statement)?;
             results.push(result);
         }
         Ok(results)
     }
 
+    // Evaluates a snippet against the current bindings and returns the value of
+    // its last statement, `Unit` when there is none. Bindings it makes are kept.
+    pub fn eval_expr_str(&mut self, source: &str) -> Result<EvaluationValue> {
+        let mut program = Parser::parse_program(Lexer::new(source).get_tokens()?)?;
+        let results = self.evaluate_program(&mut program)?;
+        Ok(program
+            .statements
+            .iter()
+            .zip(results)
+            .filter(|(statement, _)| {
+                matches!(
+                    statement,
+                    Statement::Binding { .. } | Statement::ExpressionStmt(_)
+                )
+            })
+            .map(|(_, result)| result)
+            .last()
+            .unwrap_or(EvaluationValue::Unit))
+    }
+
+    // Runtime error at `span`, with the application chain that led there.
+    fn error(&self, message: impl Into<String>, span: Span) -> Diagnostic {
+        Diagnostic::new(message, span).with_trace(self.call_stack.clone())
+    }
+
+    // Counts one evaluation step against the configured limits.
+    fn tick(&mut self, span: Span) -> Result<()> {
+        self.steps += 1;
+        match self.config.exhausted(self.steps, self.deadline) {
+            Some((message, hint)) => Err(self.error(message, span).with_hint(hint)),
+            None => Ok(()),
+        }
+    }
+
     fn evaluate_statement(&mut self, statement: &Statement) -> Result<EvaluationValue> {
         match statement {
             Statement::Binding { name, value } => {
-                let evaluated_value = self.evaluate_expr(value, Rc::clone(&self.env))?;
-                self.env
-                    .borrow_mut()
-                    .set(name.clone(), evaluated_value.clone());
+                let evaluated_value = self.evaluate_expr(value, None)?;
+                let slot = self.resolver.slot(name).expect("resolved binding");
+                self.globals[slot] = Some(evaluated_value.clone());
                 Ok(evaluated_value)
             }
-            Statement::ExpressionStmt(expr) => self.evaluate_expr(expr, Rc::clone(&self.env)),
-            Statement::Comment(_) | Statement::Eof => Ok(EvaluationValue::Unit),
+            Statement::ExpressionStmt(expr) => self.evaluate_expr(expr, None),
+            Statement::Comment { .. } | Statement::Eof => Ok(EvaluationValue::Unit),
         }
     }
     // Evaluate expression in the given environment
     fn evaluate_expr(&mut self, expr: &Expr, env: Environment) -> Result<EvaluationValue> {
-        match expr {
-            // Expr::Literal(literal) if *literal == 0. => Ok(EvaluationValue::Literal(*literal)),
-            Expr::Literal(literal) => Ok(EvaluationValue::Literal(*literal)),
+        self.tick(expr.span)?;
+        match &expr.kind {
+            // ExprKind::Literal(literal) if *literal == 0. => Ok(EvaluationValue::Literal(*literal)),
+            ExprKind::Literal(literal) => Ok(EvaluationValue::Literal(*literal)),
+            ExprKind::String(text) => Ok(EvaluationValue::String(Rc::clone(text))),
+            ExprKind::List { elements, rest } => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(self.evaluate_expr(element, env.clone())?);
+                }
+                let rest = match rest {
+                    Some(rest) => match self.evaluate_expr(rest, env)? {
+                        EvaluationValue::List(list) => list,
+                        found => {
+                            return Err(self
+                                .error("expected a list after '..'", rest.span)
+                                .with_found(found));
+                        }
+                    },
+                    None => List::new(),
+                };
+                Ok(EvaluationValue::List(List::with_rest(values, &rest)))
+            }
 
-            Expr::Identifier(name) => match env.borrow().get(name) {
-                Some(val) => Ok(val),
-                None => bail!("Unbound binding: {}", name),
-            },
-            Expr::BinaryOperation { op, lhs, rhs } => self.evaluate_binary(op, lhs, rhs, env),
-            Expr::Abstraction { param, body } => {
-                Ok(EvaluationValue::Closer(Rc::new(Abstraction {
-                    param: param.clone(),
-                    body: body.clone(),
-                    env: Rc::clone(&env),
-                })))
+            ExprKind::Identifier { name, binding } => {
+                let value = match binding {
+                    Binding::Local { depth } => Scope::get(&env, *depth),
+                    Binding::Global { index } => self.globals[*index].as_ref(),
+                    Binding::Unresolved => None,
+                };
+                match value {
+                    Some(value) => Ok(value.clone()),
+                    // A global used by an abstraction applied before the global is bound.
+                    None => Err(self.error(format!("unbound binding '{}'", name), expr.span)),
+                }
             }
-            Expr::Application { func, arg } => {
-                self.evaluate_appliation(func, arg, Rc::clone(&env), false)
+            ExprKind::BinaryOperation { op, lhs, rhs } => self.evaluate_binary(op, lhs, rhs, env),
+            ExprKind::UnaryOperation { op, operand } => match self.evaluate_expr(operand, env)? {
+                EvaluationValue::Literal(number) => Ok(EvaluationValue::Literal(op.apply(number))),
+                found => Err(self
+                    .error(
+                        "expected numeric literal for unary operations",
+                        operand.span,
+                    )
+                    .with_found(found)),
+            },
+            ExprKind::Abstraction {
+                param,
+                builtin,
+                body,
+            } => Ok(EvaluationValue::Closer(Rc::new(Abstraction {
+                param: Rc::clone(param),
+                body: Rc::clone(body),
+                env: env.clone(),
+                builtin: builtin.then(|| self.builtins.get(param)).flatten(),
+            }))),
+            ExprKind::Application { func, arg } => {
+                self.evaluate_appliation(func, arg, expr.span, env)
             }
-            Expr::ApplicationIf { func, arg1, arg2 } => {
-                self.evaluate_appliationif(func, arg1, arg2, Rc::clone(&env))
+            ExprKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => self.evaluate_if(condition, then_branch, else_branch, env),
+            ExprKind::Let { value, body, .. } => {
+                let value = self.evaluate_expr(value, env.clone())?;
+                self.evaluate_expr(body, Scope::inner(env, value))
             }
-            Expr::Recursion(args) => Ok(EvaluationValue::Recursion(args.clone())),
+            ExprKind::Recursion(args) => Ok(EvaluationValue::Recursion(Rc::clone(args), env)),
         }
     }
 
-    fn evaluate_appliationif(
+    fn evaluate_if(
         &mut self,
-        func: &Box<Expr>,
-        arg1: &Box<Expr>,
-        arg2: &Box<Expr>,
+        condition: &Expr,
+        then_branch: &Expr,
+        else_branch: &Expr,
         env: Environment,
     ) -> Result<EvaluationValue> {
-        let evaluated_func_value = self.evaluate_appliation(&func, arg1, Rc::clone(&env), false)?;
-        let arg2 = self.evaluate_expr(&arg2, Rc::clone(&env))?;
-        match evaluated_func_value {
-            EvaluationValue::Literal(1.) => Ok(arg2),
-            EvaluationValue::Literal(_) => Ok(EvaluationValue::HALT),
-            _ => bail!("λif only takes numeric value"),
+        match self.evaluate_expr(condition, env.clone())? {
+            EvaluationValue::Literal(0.) => self.evaluate_expr(else_branch, env),
+            EvaluationValue::Literal(_) => self.evaluate_expr(then_branch, env),
+            EvaluationValue::HALT => Ok(EvaluationValue::HALT),
+            found => Err(self
+                .error("expected numeric literal for the condition", condition.span)
+                .with_found(found)),
         }
     }
 
     fn evaluate_binary(
         &mut self,
         op: &BinaryOp,
-        lhs: &Box<Expr>,
-        rhs: &Box<Expr>,
+        lhs: &Expr,
+        rhs: &Expr,
         env: Environment,
     ) -> Result<EvaluationValue> {
-        let lhs_result = self.evaluate_expr(lhs, Rc::clone(&env))?;
-        let rhs_result = self.evaluate_expr(rhs, Rc::clone(&env))?;
-        let (EvaluationValue::Literal(l), EvaluationValue::Literal(r)) = (lhs_result, rhs_result)
-        else {
-            bail!("Expected numeric literal for binary operations")
-        };
-        let result = match op {
-            BinaryOp::Add => l + r,
-            BinaryOp::Sub => l - r,
-            BinaryOp::Mul => l * r,
-            BinaryOp::Div => l / r,
-            BinaryOp::BitAnd => ((l as u64) & (r as u64)) as f64,
-            BinaryOp::BitOr => ((l as u64) | (r as u64)) as f64,
+        let lhs_result = self.evaluate_expr(lhs, env.clone())?;
+        let rhs_result = self.evaluate_expr(rhs, env.clone())?;
+        if let Some(equal) = equal(&lhs_result, &rhs_result)
+            && let Some(result) = op.apply_equality(equal)
+        {
+            return Ok(EvaluationValue::Literal(result));
+        }
+        let (l, r) = match (lhs_result, rhs_result) {
+            (EvaluationValue::Literal(l), EvaluationValue::Literal(r)) => (l, r),
+            (EvaluationValue::Literal(_), found) => {
+                return Err(self
+                    .error("expected numeric literal for binary operations", rhs.span)
+                    .with_found(found));
+            }
+            (found, _) => {
+                return Err(self
+                    .error("expected numeric literal for binary operations", lhs.span)
+                    .with_found(found));
+            }
         };
-        Ok(EvaluationValue::Literal(result))
+        Ok(EvaluationValue::Literal(op.apply(l, r)))
     }
 
+    // 𝑓 does not call back into this function, it loops instead: every
+    // iteration re-applies the same abstraction to the value 𝑓 produced, so
+    // counting down from a million runs in constant Rust stack space.
     fn evaluate_appliation(
         &mut self,
-        func: &Box<Expr>,
-        arg: &Box<Expr>,
+        func: &Expr,
+        arg: &Expr,
+        span: Span,
         env: Environment,
-        recursion_context: bool,
     ) -> Result<EvaluationValue> {
         // can be func, just want make them equal in length, ahh equal length 😭
-        let evaluated_fun_value = self.evaluate_expr(&func, Rc::clone(&env))?;
-        let evaluated_arg_value = self.evaluate_expr(&arg, Rc::clone(&env))?;
+        let evaluated_fun_value = self.evaluate_expr(func, env.clone())?;
+        let mut evaluated_arg_value = self.evaluate_expr(arg, env.clone())?;
 
-        if recursion_context {
-            if let EvaluationValue::Literal(0.) = evaluated_arg_value {
+        let abstraction = match evaluated_fun_value {
+            EvaluationValue::Closer(abstraction) => abstraction,
+            EvaluationValue::Literal(literal) => return Ok(EvaluationValue::Literal(literal)),
+            EvaluationValue::Unit => return Ok(EvaluationValue::Unit),
+            EvaluationValue::HALT => return Ok(EvaluationValue::HALT),
+            found => {
+                return Err(self
+                    .error("unexpected evaluation value", func.span)
+                    .with_found(found));
+            }
+        };
+
+        // How many times 𝑓 has re-applied the abstraction so far.
+        let mut recursion_depth = 0;
+        loop {
+            if recursion_depth > 0
+                && let EvaluationValue::Literal(0.) = evaluated_arg_value
+            {
                 return Ok(EvaluationValue::HALT);
             }
-        }
-        match evaluated_fun_value {
-            EvaluationValue::Closer(abstraction) => {
-                // Scope of abstraciton diffrs from the global context/scope, creating new scope/environment;
-                // Where, current env is a captured env.
-                // if the abbtraction were to be applied from another abstraction, then it no longer can access
-                // gloabl abstraction so, putting the previously captured environment.
-                let new_env = Scope::inner(Rc::clone(&abstraction.env));
-
-                // binding parameters.
-                new_env
-                    .borrow_mut()
-                    .set(abstraction.param.clone(), evaluated_arg_value);
-
-                let mut func_result = self.evaluate_expr(&abstraction.body, Rc::clone(&new_env))?;
-
-                let mut recursion_args = None;
-                if let EvaluationValue::Recursion(rec_args) = func_result {
-                    func_result = self.evaluate_expr(&rec_args, Rc::clone(&new_env))?;
-                    // Only a single depth and a valid literal or halt signal is allowed.
-                    match func_result {
-                        EvaluationValue::Literal(_) | EvaluationValue::HALT => (),
-                        _ => bail!("Recursion(𝑓) only takes numeric value."),
-                    }
-                    // Store for later use so, it can ran after abstraction has been evaluated.
-                    recursion_args = Some(rec_args);
-                }
 
-                if matches!(func_result, EvaluationValue::HALT) {
-                    return Ok(EvaluationValue::HALT);
-                }
+            // Scope of abstraciton diffrs from the global context/scope, creating new scope/environment;
+            // Where, current env is a captured env.
+            // if the abbtraction were to be applied from another abstraction, then it no longer can access
+            // gloabl abstraction so, putting the previously captured environment.
+            // The parameter is bound at depth 0 of it.
+            let new_env = Scope::inner(abstraction.env.clone(), evaluated_arg_value);
 
-                // Kinda bit of redundancy
-                let func_evalution_result = match abstraction.param.as_str() {
-                    "ascii" => match func_result {
-                        EvaluationValue::Literal(ascii) if ascii >= 255.0 => bail!(
-                            "λascii only takes ASCII values in decimal form, ranging from 0 to 255."
-                        ),
-                        EvaluationValue::Literal(ascii) => {
-                            abstractions::abstraction_ascii(ascii as u8)
-                        }
-                        _ => bail!(
-                            "λascii only takes ASCII values in decimal form, ranging from 0 to 255.",
+            if let Some(max_depth) = self.config.max_depth
+                && self.call_stack.len() >= max_depth
+            {
+                return Err(self
+                    .error(
+                        format!(
+                            "fuel exhausted, applications nested deeper than {}",
+                            max_depth
                         ),
-                    },
-                    "input" => match func_result {
-                        EvaluationValue::Literal(0.) => abstractions::abstraction_input_char(),
+                        span,
+                    )
+                    .with_hint("the depth limit is set with --max-depth"));
+            }
+            // Stacks grow down on every platform this runs on.
+            if self.stack_base.saturating_sub(stack_address())
+                > self.config.max_stack.unwrap_or(DEFAULT_MAX_STACK)
+            {
+                return Err(self
+                    .error("fuel exhausted, applications nested too deeply", span)
+                    .with_hint(
+                        "the tree-walking interpreter recurses on the native stack, --vm doesn't",
+                    ));
+            }
+            self.call_stack.push(TraceFrame {
+                name: Rc::clone(&abstraction.param),
+                builtin: abstraction.builtin.is_some(),
+                span,
+                recursion_depth,
+            });
 
-                        EvaluationValue::Literal(1.) => abstractions::abstraction_input_numeric(),
-                        EvaluationValue::Literal(_) => {
-                            bail!("λinput only takes numeric value either, 0, or 1.")
-                        }
-                        _ => bail!("λinput only takes numeric value either, 0, or 1.",),
-                    },
-                    "time" => abstractions::abstraction_time(),
-                    "print" => match func_result {
-                        EvaluationValue::Literal(numeric_value) => {
-                            abstractions::abstraction_print(numeric_value)
-                        }
-                        _ => bail!("λraw only takes numeric value."),
-                    },
+            let mut func_result = self.evaluate_expr(&abstraction.body, new_env)?;
 
-                    "sleep" => match func_result {
-                        EvaluationValue::Literal(numeric_value) => {
-                            abstractions::abstraction_sleep(numeric_value)
-                        }
-                        _ => bail!("λraw only takes numeric value."),
-                    },
-                    _ => Ok(func_result),
-                };
+            let mut recursion_arg = None;
+            if let EvaluationValue::Recursion(rec_args, rec_env) = func_result {
+                func_result = self.evaluate_expr(&rec_args, rec_env)?;
+                // Only a single depth and a valid literal or halt signal is allowed.
+                match func_result {
+                    EvaluationValue::Literal(_) | EvaluationValue::HALT => (),
+                    found => {
+                        return Err(self
+                            .error("recursion (𝑓) only takes numeric value", rec_args.span)
+                            .with_found(found));
+                    }
+                }
+                // Store for later use so, it can ran after abstraction has been evaluated.
+                recursion_arg = Some(func_result.clone());
+            }
+
+            if matches!(func_result, EvaluationValue::HALT) {
+                self.call_stack.pop();
+                return Ok(EvaluationValue::HALT);
+            }
+
+            let func_evalution_result = self.apply_builtin(&abstraction, func_result, span);
+            self.call_stack.pop();
 
-                if let Some(rec_args) = recursion_args {
-                    return self.evaluate_appliation(&func, &rec_args, Rc::clone(&new_env), true);
+            match recursion_arg {
+                Some(next_arg) => {
+                    func_evalution_result?;
+                    evaluated_arg_value = next_arg;
+                    recursion_depth += 1;
                 }
-                func_evalution_result
+                None => return func_evalution_result,
             }
-            EvaluationValue::Literal(literal) => Ok(EvaluationValue::Literal(literal)),
-            EvaluationValue::Unit => Ok(EvaluationValue::Unit),
-            EvaluationValue::HALT => Ok(EvaluationValue::HALT),
-            _ => bail!("Unexpected evaluation value!"),
         }
     }
+
+    // Abstractions whose parameter was a registered built-in when they were
+    // created hand whatever the body evaluated to over to it. Any other
+    // abstraction returns it unchanged.
+    fn apply_builtin(
+        &mut self,
+        abstraction: &Abstraction,
+        func_result: EvaluationValue,
+        span: Span,
+    ) -> Result<EvaluationValue> {
+        let Some(builtin) = &abstraction.builtin else {
+            return Ok(func_result);
+        };
+        if let EvaluationValue::List(list) = &func_result
+            && let Some(result) = builtin.call_list(list)
+        {
+            return Ok(result);
+        }
+        if let (EvaluationValue::String(text), Argument::Text) = (&func_result, &builtin.argument) {
+            return builtin
+                .call_text(&mut self.host, text)
+                .map_err(|e| self.error(e.to_string(), span));
+        }
+        let number = match func_result {
+            EvaluationValue::Literal(number) => Some(number),
+            _ => None,
+        };
+        let Some(argument) = builtin.argument.check(number) else {
+            return Err(self
+                .error(
+                    format!("λ@{} only takes {}", abstraction.param, builtin.expects),
+                    span,
+                )
+                .with_found(func_result));
+        };
+        builtin
+            .call(&mut self.host, argument)
+            .map_err(|e| self.error(e.to_string(), span))
+    }
+}
+
+impl Element for EvaluationValue {
+    fn number(number: f64) -> Self {
+        EvaluationValue::Literal(number)
+    }
+
+    fn list(list: List<Self>) -> Self {
+        EvaluationValue::List(list)
+    }
+
+    fn comparable(&self) -> Option<Comparable<'_, Self>> {
+        match self {
+            EvaluationValue::Literal(number) => Some(Comparable::Number(*number)),
+            EvaluationValue::String(text) => Some(Comparable::Text(text)),
+            EvaluationValue::List(list) => Some(Comparable::List(list)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::MemoryHost;
+
+    // Runs `source` with scripted `input`, returning the statement results and
+    // everything the program wrote.
+    fn run(source: &str, input: &str) -> (Vec<EvaluationValue>, String) {
+        let tokens = Lexer::new(source).get_tokens().unwrap();
+        let mut program = Parser::parse_program(tokens).unwrap();
+        let mut interpreter =
+            Interpreter::with_host(MemoryHost::new(input), InterpreterConfig::default())
+                .with_builtins(BuiltinRegistry::standard());
+        let results = interpreter.evaluate_program(&mut program).unwrap();
+        (results, interpreter.host().output_string())
+    }
+
+    // Like `run`, for a program that stops with an error, returning the error
+    // and what was written before it.
+    fn run_failing(source: &str) -> (Diagnostic, String) {
+        let tokens = Lexer::new(source).get_tokens().unwrap();
+        let mut program = Parser::parse_program(tokens).unwrap();
+        let mut interpreter =
+            Interpreter::with_host(MemoryHost::new(""), InterpreterConfig::default())
+                .with_builtins(BuiltinRegistry::standard());
+        let error = interpreter.evaluate_program(&mut program).unwrap_err();
+        (error, interpreter.host().output_string())
+    }
+
+    #[test]
+    fn arithmetic_rounds_and_shifts_as_documented() {
+        let expressions = [
+            "0 - 7 % 3",
+            "(0 - 7) % 3",
+            "7 % (0 - 3)",
+            "5.5 % 2",
+            "(0 - 7) div 2",
+            "2 ** 3 ** 2",
+            "(0 - 2) ** 2",
+            "(0 - 1) & 3",
+            "5 ^ 3",
+            "1 << 3",
+            "(0 - 16) >> 2",
+            "1 << (0 - 1)",
+            "1 << 64",
+            "(0 - 1) >> 64",
+            "6.9 | 0",
+        ];
+        let (results, _) = run(&expressions.join("\n"), "");
+        let results = results[..expressions.len()]
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            [
+                "-1", "2", "-2", "1.5", "-4", "512", "4", "3", "6", "8", "-4", "0", "0", "-1", "6"
+            ]
+        );
+    }
+
+    #[test]
+    fn comparisons_and_logic_evaluate_to_1_or_0() {
+        let source = "x = 3\n(λ@print. x == 3) 0 (λ@print. x != 3) 0 (λ@print. x < 3) 0\n\
+                      (λ@print. x <= 3) 0 (λ@print. 2 + 2 > x * 1) 0 (λ@print. x >= 4) 0\n\
+                      (λ@print. x > 1 && x < 5) 0 (λ@print. 0 || !x) 0 (λ@print. !(x - 3)) 0\n\
+                      (λ@print. x == 3 == 1) 0";
+        let (_, output) = run(source, "");
+        assert_eq!(output, "1001101011");
+        // Only numbers can be negated.
+        let (_, output) = run_failing("(λ@print. 1) 0 (λ@print. !λv. v) 0");
+        assert_eq!(output, "1");
+    }
+
+    #[test]
+    fn conditionals_only_run_the_branch_taken() {
+        // λ@ascii would print an A from a branch that isn't taken.
+        let source = "x = 3\n(λ@print. if x > 2 then 1 else (λ@ascii. 65) 0) 0\n\
+                      (λ@print. if x - 3 then (λ@ascii. 65) 0 else 2) 0\n\
+                      (λ@print. if print > 0 then 𝑓(print - 1) else 0) 3\n\
+                      (λ@print. if (λn. 𝑓(0)) 1 then 1 else 2) 0";
+        let (_, output) = run(source, "");
+        assert_eq!(output, "12210");
+
+        let (_, output) = run_failing("(λ@print. 1) 0 if λv. v then 1 else 2");
+        assert_eq!(output, "1");
+    }
+
+    #[test]
+    fn strings_are_written_whole() {
+        let source = "hello = \"Hello, \\\"World\\\"! \\u{3bb}\\n\"\n\
+                      (λ@write. hello) 0 (λ@write. \"a\\tb\\\\\") 0";
+        let (_, output) = run(source, "");
+        assert_eq!(output, "Hello, \"World\"! λ\na\tb\\");
+
+        let (_, output) = run_failing("(λ@write. \"a\") 0\n(λ@print. \"b\") 0");
+        assert_eq!(output, "a");
+        let (_, output) = run_failing("(λ@write. 1) 0");
+        assert_eq!(output, "");
+    }
+
+    #[test]
+    fn code_points_go_in_and_out_whole() {
+        let source = "(λ@char. (λ@input. input) 2) 0 (λ@char. (λ@input. input) 2) 0\n\
+                      (λ@print. (λ@input. input) 2) 0 (λ@char. 128512) 0 (λ@ascii. 255) 0";
+        let (_, output) = run(source, "λé😀");
+        assert_eq!(output, "λé128512😀\u{fffd}");
+
+        let (_, output) = run_failing("(λ@char. 65) 0 (λ@char. 55296) 0");
+        assert_eq!(output, "A");
+    }
+
+    #[test]
+    fn lists_hold_any_value_and_compare_by_value() {
+        let source = "xs = [1, 2 + 1, \"three\", [4],]\nys = [0, ..xs]\n\
+                      sum = λl. if (λ@length. l) 0 then ((λ@head. l) 0) + (sum) (λ@tail. l) 0 else 0\n\
+                      (λ@print. (λ@length. ys) 0) 0 (λ@print. (λ@head. (λ@tail. ys) 0) 0) 0\n\
+                      (λ@print. (sum) [1, 2, 3, 4]) 0 (λ@print. ys == [0, 1, 3, \"three\", [4]]) 0\n\
+                      (λ@print. xs == ys) 0 (λ@print. [1] != 1) 0 (λ@print. (λ@length. []) 0) 0";
+        let (_, output) = run(source, "");
+        assert_eq!(output, "51101010");
+
+        for source in [
+            "(λ@print. 1) 0 (λ@print. [1] + 1) 0",
+            "(λ@print. 1) 0 (λ@head. []) 0",
+            "(λ@print. 1) 0 [1, ..2]",
+            "(λ@print. 1) 0 (λ@print. [λx. x] == [1]) 0",
+        ] {
+            let (_, output) = run_failing(source);
+            assert_eq!(output, "1", "{}", source);
+        }
+    }
+
+    #[test]
+    fn let_binds_a_name_in_its_body_only() {
+        let source = "hyp = λa b. let aa = a * a in let bb = b * b in aa + bb\nx = 1\n\
+                      (λ@print. (hyp) 3 4) 0 (λ@print. let n = 5 in let f = λx. x + n in (f) 10) 0\n\
+                      (λ@print. let print = print - 1 in if print > 0 then 𝑓(print) else 0) 3\n\
+                      (λ@print. let x = x + 1 in let x = x * 10 in x) 0 (λ@print. x) 0\n\
+                      (λ@print. (λ@head. [let a = 7 in a, 2]) 0) 0";
+        let (_, output) = run(source, "");
+        assert_eq!(output, "25152102017");
+
+        let (error, _) = run_failing("(λ@print. let y = 2 in y) 0\n(λ@print. y) 0");
+        assert_eq!(error.message, "unbound binding 'y'");
+    }
+
+    #[test]
+    fn deep_recursion_runs_in_constant_stack() {
+        // Small enough that one native frame per iteration would overflow it.
+        let handle = std::thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(|| matches!(run("(λn. 𝑓(n-1)) 1000000", "").0[0], EvaluationValue::HALT))
+            .unwrap();
+        assert!(handle.join().unwrap());
+    }
+
+    #[test]
+    fn builtins_go_through_the_host() {
+        let source = "n = (λ@input. input) 1\n(λ@print. n * 2) 0\n(λ@ascii. (λ@input. input) 0) 0";
+        let (results, output) = run(source, "21 !");
+        assert_eq!(output, "42!");
+        // Input has run out by now.
+        let (_, output) = run("(λ@print. (λ@input. input) 1) 0", "");
+        assert_eq!(output, "");
+        assert!(matches!(results[1], EvaluationValue::Literal(42.)));
+    }
+
+    #[test]
+    fn input_halts_once_it_runs_out() {
+        // Characters, numbers and code points.
+        let source = "(λ@input. input) 0\n(λ@input. input) 1\n(λ@input. input) 2";
+        let mut program = Parser::parse_program(Lexer::new(source).get_tokens().unwrap()).unwrap();
+        let interpreted =
+            Interpreter::with_host(MemoryHost::new(" "), InterpreterConfig::default())
+                .with_builtins(BuiltinRegistry::standard())
+                .evaluate_program(&mut program)
+                .unwrap();
+        let compiled = crate::vm::Vm::with_host(MemoryHost::new(" "), InterpreterConfig::default())
+            .with_builtins(BuiltinRegistry::standard())
+            .evaluate_program(&mut program)
+            .unwrap();
+        let interpreted = interpreted[..3].iter().map(|value| value.to_string());
+        let compiled = compiled[..3].iter().map(|value| value.to_string());
+        assert_eq!(interpreted.collect::<Vec<_>>(), ["32", "HALT", "HALT"]);
+        assert_eq!(compiled.collect::<Vec<_>>(), ["32", "HALT", "HALT"]);
+    }
+
+    #[test]
+    fn list_built_in_names_are_ordinary_names_without_the_at() {
+        // Programs written before head, tail and length were built-ins.
+        let source = "length = λhead. head + 1\n(λlength. length * 2) 21\n\
+                      (λtail. tail) [1, 2]\n(λ@print. (length) 2) 0";
+        let (results, output) = run(source, "");
+        assert_eq!(output, "3");
+        let results = results[..3].iter().map(|value| value.to_string());
+        assert_eq!(results.collect::<Vec<_>>(), ["<λhead>", "42", "[1, 2]"]);
+    }
+
+    #[test]
+    fn builtins_come_from_the_registry() {
+        use crate::builtins::Argument;
+
+        let source = "(λprint. 2) 0\n(λ@twice. 21) 0";
+        let mut interpreter =
+            Interpreter::with_host(MemoryHost::new(""), InterpreterConfig::default());
+        interpreter.builtins_mut().register(
+            "twice",
+            Builtin::new(Argument::Number, "numeric value.", |host, n| {
+                host.write_number(n * 2.)?;
+                Ok(EvaluationValue::Literal(n * 2.))
+            }),
+        );
+        let tokens = Lexer::new(source).get_tokens().unwrap();
+        let mut program = Parser::parse_program(tokens).unwrap();
+        let results = interpreter.evaluate_program(&mut program).unwrap();
+        // Without the @ λprint is an ordinary abstraction.
+        assert_eq!(interpreter.host().output_string(), "42");
+        assert!(matches!(results[0], EvaluationValue::Literal(2.)));
+        assert!(matches!(results[1], EvaluationValue::Literal(42.)));
+
+        // And with it, only what's registered can be opted into.
+        let tokens = Lexer::new("(λ@print. 2) 0").get_tokens().unwrap();
+        let mut program = Parser::parse_program(tokens).unwrap();
+        let error = interpreter.evaluate_program(&mut program).unwrap_err();
+        assert_eq!(error.message, "unknown built-in '@print'");
+        assert_eq!(error.hint.as_deref(), Some("the built-ins are @twice"));
+        assert_eq!(interpreter.host().output_string(), "42");
+    }
+
+    #[test]
+    fn runtime_errors_trace_the_active_applications() {
+        let source = "count = λn. if n then (count) n - 1 else (λ@print. \"zero\") 0\n\
+                      (λk. if k > 1 then 𝑓(k - 1) else (count) 2) 3";
+        let mut program = Parser::parse_program(Lexer::new(source).get_tokens().unwrap()).unwrap();
+        let error = Interpreter::with_host(MemoryHost::new(""), InterpreterConfig::default())
+            .with_builtins(BuiltinRegistry::standard())
+            .evaluate_program(&mut program)
+            .unwrap_err();
+        let rendered = error.render(source);
+        assert!(
+            rendered.ends_with(
+                "  = trace, innermost last:\n      \
+                 λk applied at 2:1, 𝑓 depth 2\n      \
+                 λn applied at 2:34\n      \
+                 λn applied at 1:23 (2 times)\n      \
+                 λ@print applied at 1:42\n"
+            ),
+            "{}",
+            rendered
+        );
+        let vm_error = crate::vm::Vm::with_host(MemoryHost::new(""), InterpreterConfig::default())
+            .with_builtins(BuiltinRegistry::standard())
+            .evaluate_program(&mut program)
+            .unwrap_err();
+        assert_eq!(vm_error.render(source), rendered);
+    }
+
+    // Runs `source` on the interpreter and on the VM under `config`, returning
+    // the error each of them stopped with.
+    fn exhaust(source: &str, config: InterpreterConfig) -> (Diagnostic, Diagnostic) {
+        let mut program = Parser::parse_program(Lexer::new(source).get_tokens().unwrap()).unwrap();
+        let interpreted = Interpreter::with_host(MemoryHost::new(""), config.clone())
+            .with_builtins(BuiltinRegistry::standard())
+            .evaluate_program(&mut program)
+            .unwrap_err();
+        let compiled = crate::vm::Vm::with_host(MemoryHost::new(""), config)
+            .with_builtins(BuiltinRegistry::standard())
+            .evaluate_program(&mut program)
+            .unwrap_err();
+        (interpreted, compiled)
+    }
+
+    #[test]
+    fn step_limit_stops_both_backends() {
+        let config = InterpreterConfig {
+            max_steps: Some(100),
+            ..InterpreterConfig::default()
+        };
+        let (interpreted, compiled) = exhaust("(λn. 𝑓(n + 1)) 1", config);
+        for error in [&interpreted, &compiled] {
+            assert_eq!(error.message, "fuel exhausted after 100 evaluation steps");
+            assert_eq!(
+                error.hint.as_deref(),
+                Some("the step limit is set with --max-steps")
+            );
+        }
+        // `n + 1`, the VM gets there counting instructions rather than
+        // expressions.
+        assert_eq!(interpreted.span, Span::new(11, 16, 1, 8));
+        assert_eq!(compiled.span, Span::new(11, 16, 1, 8));
+    }
+
+    #[test]
+    fn depth_limit_stops_both_backends() {
+        let config = InterpreterConfig {
+            max_depth: Some(10),
+            ..InterpreterConfig::default()
+        };
+        let source = "count = λn. if n then 1 + (count) n - 1 else 0
+(count) 100";
+        let (interpreted, compiled) = exhaust(source, config);
+        for error in [&interpreted, &compiled] {
+            assert_eq!(
+                error.message,
+                "fuel exhausted, applications nested deeper than 10"
+            );
+            assert_eq!(
+                error.hint.as_deref(),
+                Some("the depth limit is set with --max-depth")
+            );
+            // `(count) n - 1`
+            assert_eq!(error.span, Span::new(27, 40, 1, 27));
+            assert_eq!(error.trace.len(), 10);
+        }
+    }
+
+    #[test]
+    fn timeout_stops_both_backends() {
+        let config = InterpreterConfig {
+            timeout: Some(Duration::ZERO),
+            ..InterpreterConfig::default()
+        };
+        let (interpreted, compiled) = exhaust("(λn. 𝑓(n + 1)) 1", config);
+        for error in [&interpreted, &compiled] {
+            assert_eq!(error.message, "fuel exhausted after 0 ms");
+            assert_eq!(
+                error.hint.as_deref(),
+                Some("the time limit is set with --timeout")
+            );
+        }
+        // `𝑓(n + 1)`, the clock is read on the 1024th step.
+        assert_eq!(interpreted.span, Span::new(6, 17, 1, 6));
+        // `n`, an instruction rather than an expression.
+        assert_eq!(compiled.span, Span::new(11, 12, 1, 8));
+    }
+
+    #[test]
+    fn deep_plain_recursion_stops_before_the_native_stack_does() {
+        let source = "count = λn. if n then 1 + (count) n - 1 else 0
+(count) 1000000";
+        let handle = std::thread::Builder::new()
+            .stack_size(2 * DEFAULT_MAX_STACK)
+            .spawn(move || {
+                let mut program =
+                    Parser::parse_program(Lexer::new(source).get_tokens().unwrap()).unwrap();
+                let error =
+                    Interpreter::with_host(MemoryHost::new(""), InterpreterConfig::default())
+                        .evaluate_program(&mut program)
+                        .unwrap_err();
+                (error.message.clone(), error.span)
+            })
+            .unwrap();
+        let (message, span) = handle.join().unwrap();
+        assert_eq!(message, "fuel exhausted, applications nested too deeply");
+        assert_eq!(span, Span::new(27, 40, 1, 27));
+    }
+
+    #[test]
+    fn runaway_recursion_stops_both_backends() {
+        // `(fib) n - 1 + (fib) n - 2` applies fib to all of `n - 1 + ...`, so
+        // this never gets to the base case.
+        let source = "fib = λn. if n < 2 then n else (fib) n - 1 + (fib) n - 2\n(fib) 10";
+        let handle = std::thread::Builder::new()
+            .stack_size(2 * DEFAULT_MAX_STACK)
+            .spawn(move || {
+                let (interpreted, compiled) = exhaust(source, InterpreterConfig::default());
+                [interpreted, compiled].map(|error| (error.message.clone(), error.span))
+            })
+            .unwrap();
+        for (message, span) in handle.join().unwrap() {
+            assert_eq!(message, "fuel exhausted, applications nested too deeply");
+            assert_eq!(span, Span::new(46, 57, 1, 46));
+        }
+    }
+
+    #[test]
+    fn unbound_names_are_reported_before_running() {
+        let tokens = Lexer::new("(λ@print. 1) 0\n(λ@print. y) 0\ny = 2")
+            .get_tokens()
+            .unwrap();
+        let mut program = Parser::parse_program(tokens).unwrap();
+        let mut interpreter =
+            Interpreter::with_host(MemoryHost::new(""), InterpreterConfig::default())
+                .with_builtins(BuiltinRegistry::standard());
+        let error = interpreter.evaluate_program(&mut program).unwrap_err();
+        assert_eq!(error.message, "unbound binding 'y'");
+        assert_eq!(
+            error.hint.as_deref(),
+            Some("'y' is only bound further down, at line 3")
+        );
+        assert_eq!(interpreter.host().output_string(), "");
+
+        // Not "further down" when it's the statement's own name.
+        let tokens = Lexer::new("a = 1\nc = (a) c").get_tokens().unwrap();
+        let mut program = Parser::parse_program(tokens).unwrap();
+        let error = interpreter.evaluate_program(&mut program).unwrap_err();
+        assert_eq!(error.message, "unbound binding 'c'");
+        assert_eq!(
+            error.hint.as_deref(),
+            Some(
+                "'c' is what this statement binds, only an abstraction in its value can refer to it"
+            )
+        );
+
+        // Bodies that aren't applied right away may use later bindings.
+        let (_, output) = run("f = λx. g + x\ng = 40\n(λ@print. (f) 2) 0", "");
+        assert_eq!(output, "42");
+    }
 }
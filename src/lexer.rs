@@ -1,9 +1,8 @@
-use std::{fs, io::ErrorKind, path::PathBuf};
+use std::fmt;
 
+use super::error::{Diagnostic, Span};
 use super::throw_lexer_syntax_error;
 
-
-
 // Variable convection for lexer:
 // prefix:"consume" => Sets (current end character of either string's or character's offset)+1;
 // prefix:"look" => Doesn't increase offset;
@@ -14,12 +13,31 @@ pub enum Operator {
     Minus,
     Asterisk,
     Slash,
+    Percent,
+    DoubleAsterisk,
+    Caret,
+    ShiftLeft,
+    ShiftRight,
+    // Integer division, a keyword since `//` starts a comment.
+    Div,
     Equal,
     LeftParen,
     RightParen,
+    LeftBracket,
+    RightBracket,
+    Comma,
     Dot,
     BitAnd,
     BitOr,
+    EqualEqual,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+    Not,
 }
 
 #[derive(Debug, PartialEq)]
@@ -27,62 +45,160 @@ pub enum TokenKind {
     Comment(String),
     Lamda,
     Recursion,
+    If,
+    Then,
+    Else,
+    Let,
+    In,
     Identifier(String),
+    // `@name`, only valid as a parameter, where it opts into the built-in.
+    Builtin(String),
     Literal(f64),
+    // With its escapes already replaced.
+    String(String),
     Operator(Operator),
     Eof,
 }
 
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Asterisk => "*",
+            Operator::Slash => "/",
+            Operator::Percent => "%",
+            Operator::DoubleAsterisk => "**",
+            Operator::Caret => "^",
+            Operator::ShiftLeft => "<<",
+            Operator::ShiftRight => ">>",
+            Operator::Div => "div",
+            Operator::Equal => "=",
+            Operator::LeftParen => "(",
+            Operator::RightParen => ")",
+            Operator::LeftBracket => "[",
+            Operator::RightBracket => "]",
+            Operator::Comma => ",",
+            Operator::Dot => ".",
+            Operator::BitAnd => "&",
+            Operator::BitOr => "|",
+            Operator::EqualEqual => "==",
+            Operator::NotEqual => "!=",
+            Operator::Less => "<",
+            Operator::LessEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterEqual => ">=",
+            Operator::And => "&&",
+            Operator::Or => "||",
+            Operator::Not => "!",
+        };
+        write!(f, "'{}'", symbol)
+    }
+}
+
+// Used in diagnostics, so it reads as prose rather than Rust.
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Comment(_) => write!(f, "comment"),
+            TokenKind::Lamda => write!(f, "'λ'"),
+            TokenKind::Recursion => write!(f, "'𝑓'"),
+            TokenKind::If => write!(f, "'if'"),
+            TokenKind::Then => write!(f, "'then'"),
+            TokenKind::Else => write!(f, "'else'"),
+            TokenKind::Let => write!(f, "'let'"),
+            TokenKind::In => write!(f, "'in'"),
+            TokenKind::Identifier(name) => write!(f, "identifier '{}'", name),
+            TokenKind::Builtin(name) => write!(f, "built-in '@{}'", name),
+            TokenKind::Literal(number) => write!(f, "number {}", number),
+            TokenKind::String(text) => write!(f, "string {:?}", text),
+            TokenKind::Operator(operator) => write!(f, "{}", operator),
+            TokenKind::Eof => write!(f, "end of file"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
 pub struct Lexer {
-    tokens: Vec<char>,
+    // Each character along with its byte offset into the source.
+    tokens: Vec<(usize, char)>,
+    source_len: usize,
     offset: usize,
+    // 1-based position of the character at `offset`. Columns count characters,
+    // so 'λ' and '𝑓' are one column each and so is a tab.
     row: usize,
     col: usize,
+    // Tagged onto every span, see `with_input`.
+    input: usize,
 }
 
 impl Lexer {
-    pub fn new(source_path: PathBuf) -> Self {
-        match fs::read_to_string(source_path) {
-            Ok(source) => Self {
-                tokens: source.chars().into_iter().collect::<Vec<_>>(),
-                offset: 0,
-                row: 1,
-                col: 1,
-            },
-            Err(e) if e.kind() == ErrorKind::NotFound => {
-                panic!("File not found!")
-            }
-            Err(e) => {
-                panic!("IO Error : {:?}", e)
-            }
+    pub fn new(source: &str) -> Self {
+        Self {
+            tokens: source.char_indices().collect::<Vec<_>>(),
+            source_len: source.len(),
+            offset: 0,
+            row: 1,
+            col: 1,
+            input: 0,
         }
     }
-    pub fn get_tokens(mut self) -> Vec<TokenKind> {
+
+    // Marks the spans as being in the REPL's `input`-th input, so an error
+    // raised later, from a closure defined here, is drawn against this source.
+    pub fn with_input(mut self, input: usize) -> Self {
+        self.input = input;
+        self
+    }
+
+    pub fn get_tokens(mut self) -> Result<Vec<Token>, Diagnostic> {
         let aprox_capacity = self
             .tokens
             .iter()
-            .filter(|c| !c.is_ascii_whitespace())
+            .filter(|(_, c)| !c.is_ascii_whitespace())
             .count();
         let mut tokens = Vec::with_capacity(aprox_capacity);
-        while let Some(token) = self.get_token() {
-            match token {
+        loop {
+            self.consume_while(|c| c.is_whitespace());
+            let start = self.here();
+            let kind = self.get_token()?;
+            let span = self.span_from(start);
+            match kind {
                 TokenKind::Eof => {
-                    tokens.push(TokenKind::Eof);
+                    tokens.push(Token {
+                        kind: TokenKind::Eof,
+                        span,
+                    });
                     break;
                 }
-                token => tokens.push(token),
+                kind => tokens.push(Token { kind, span }),
             }
         }
-        tokens
+        Ok(tokens)
     }
-    fn get_token(&mut self) -> Option<TokenKind> {
-        self.consume_while(|c| c.is_whitespace());
+
+    fn get_token(&mut self) -> Result<TokenKind, Diagnostic> {
+        let start = self.here();
         if let Some(ch) = self.consume() {
             match ch {
-                '(' => Some(TokenKind::Operator(Operator::LeftParen)),
-                ')' => Some(TokenKind::Operator(Operator::RightParen)),
-
-                '*' => Some(TokenKind::Operator(Operator::Asterisk)),
+                '(' => Ok(TokenKind::Operator(Operator::LeftParen)),
+                ')' => Ok(TokenKind::Operator(Operator::RightParen)),
+                '[' => Ok(TokenKind::Operator(Operator::LeftBracket)),
+                ']' => Ok(TokenKind::Operator(Operator::RightBracket)),
+                ',' => Ok(TokenKind::Operator(Operator::Comma)),
+
+                '*' => Ok(TokenKind::Operator(self.followed_by(
+                    '*',
+                    Operator::DoubleAsterisk,
+                    Operator::Asterisk,
+                ))),
+                '%' => Ok(TokenKind::Operator(Operator::Percent)),
+                '^' => Ok(TokenKind::Operator(Operator::Caret)),
 
                 '/' => {
                     match self.look_ahead() {
@@ -94,33 +210,84 @@ impl Lexer {
                                 // some comment\r\n Something
                                 Some('\r') => {
                                     comment.pop(); //remove cr
-                                    Some(TokenKind::Comment(comment))
+                                    Ok(TokenKind::Comment(comment))
                                 }
                                 // some comment\n SOMETHING
-                                Some(_) => Some(TokenKind::Comment(comment)),
+                                Some(_) => Ok(TokenKind::Comment(comment)),
                                 // some comment\n(EOF)
-                                None => Some(TokenKind::Comment(comment)),
+                                None => Ok(TokenKind::Comment(comment)),
                             }
                         }
                         // Next char should be either white space or num.
                         // Err will thrown, while building AST.
-                        Some(_) => Some(TokenKind::Operator(Operator::Slash)),
-                        None => Some(TokenKind::Operator(Operator::Slash)),
+                        Some(_) => Ok(TokenKind::Operator(Operator::Slash)),
+                        None => Ok(TokenKind::Operator(Operator::Slash)),
                     }
                 }
 
-                '-' => Some(TokenKind::Operator(Operator::Minus)),
-                '+' => Some(TokenKind::Operator(Operator::Plus)),
-
-                '=' => Some(TokenKind::Operator(Operator::Equal)),
-                '.' => Some(TokenKind::Operator(Operator::Dot)),
-
-                '&' => Some(TokenKind::Operator(Operator::BitAnd)),
-                '|' => Some(TokenKind::Operator(Operator::BitOr)),
-
-                'λ' => Some(TokenKind::Lamda),
-
-                '𝑓' => Some(TokenKind::Recursion),
+                '-' => Ok(TokenKind::Operator(Operator::Minus)),
+                '+' => Ok(TokenKind::Operator(Operator::Plus)),
+
+                '=' => Ok(TokenKind::Operator(self.followed_by(
+                    '=',
+                    Operator::EqualEqual,
+                    Operator::Equal,
+                ))),
+                '!' => Ok(TokenKind::Operator(self.followed_by(
+                    '=',
+                    Operator::NotEqual,
+                    Operator::Not,
+                ))),
+                '<' if self.look_ahead() == Some('<') => {
+                    self.advance();
+                    Ok(TokenKind::Operator(Operator::ShiftLeft))
+                }
+                '<' => Ok(TokenKind::Operator(self.followed_by(
+                    '=',
+                    Operator::LessEqual,
+                    Operator::Less,
+                ))),
+                '>' if self.look_ahead() == Some('>') => {
+                    self.advance();
+                    Ok(TokenKind::Operator(Operator::ShiftRight))
+                }
+                '>' => Ok(TokenKind::Operator(self.followed_by(
+                    '=',
+                    Operator::GreaterEqual,
+                    Operator::Greater,
+                ))),
+                '.' => Ok(TokenKind::Operator(Operator::Dot)),
+
+                '&' => Ok(TokenKind::Operator(self.followed_by(
+                    '&',
+                    Operator::And,
+                    Operator::BitAnd,
+                ))),
+                '|' => Ok(TokenKind::Operator(self.followed_by(
+                    '|',
+                    Operator::Or,
+                    Operator::BitOr,
+                ))),
+
+                // `\x. body` and `lambda x. body` are easier to type than
+                // λ, and so is `rec(...)` than 𝑓, see the keywords below.
+                'λ' | '\\' => Ok(TokenKind::Lamda),
+
+                '𝑓' => Ok(TokenKind::Recursion),
+
+                '"' => self.string(start),
+
+                '@' => {
+                    let name = self.consume_while(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+                    if name.is_empty() {
+                        return Err(Diagnostic::new(
+                            "missing built-in name",
+                            self.span_from(start),
+                        )
+                        .with_hint("a built-in is opted into with its name, like λ@print."));
+                    }
+                    Ok(TokenKind::Builtin(name))
+                }
 
                 ch => {
                     match ch {
@@ -130,14 +297,23 @@ impl Lexer {
                             identifier.push_str(
                                 &self.consume_while(|ch| ch.is_ascii_alphanumeric() || ch == '_'),
                             );
-                            Some(TokenKind::Identifier(identifier))
+                            match identifier.as_str() {
+                                "lambda" => Ok(TokenKind::Lamda),
+                                "rec" => Ok(TokenKind::Recursion),
+                                "if" => Ok(TokenKind::If),
+                                "then" => Ok(TokenKind::Then),
+                                "else" => Ok(TokenKind::Else),
+                                "let" => Ok(TokenKind::Let),
+                                "in" => Ok(TokenKind::In),
+                                "div" => Ok(TokenKind::Operator(Operator::Div)),
+                                _ => Ok(TokenKind::Identifier(identifier)),
+                            }
                         }
 
                         ch if ch.is_ascii_digit() => {
                             let mut numeric_literal = String::from(ch);
 
-                            let mut last_was_underscore =
-                                numeric_literal.chars().next() == Some('_');
+                            let mut last_was_underscore = numeric_literal.starts_with('_');
 
                             let mut digit_underscore_filter = |ch: char| {
                                 let is_valid = ch.is_ascii_digit() || ch == '_';
@@ -162,82 +338,189 @@ impl Lexer {
                             if matches!(self.look_ahead(), Some('e') | Some('E')) {
                                 numeric_literal.push('E');
                                 self.advance();
-                                if let Some(sign) = self.look_ahead() {
-                                    if matches!(sign, '+' | '-') {
-                                        numeric_literal.push(self.consume().unwrap());
-                                    }
+                                if let Some(sign) = self.look_ahead()
+                                    && matches!(sign, '+' | '-')
+                                {
+                                    numeric_literal.push(self.consume().unwrap());
                                 }
                                 let digits = self.consume_while(&mut digit_underscore_filter);
 
                                 if digits.is_empty() {
-                                    throw_lexer_syntax_error!(
-                                        "Standard Number",
-                                        "Malformed Number",
-                                        self.row,
-                                        self.col
-                                    );
+                                    return Err(Diagnostic::new(
+                                        "malformed number",
+                                        self.span_from(start),
+                                    )
+                                    .with_expected("digits after the exponent")
+                                    .with_hint("an exponent is written like 1e10 or 2.5E-3"));
                                 }
                                 numeric_literal.push_str(&digits);
                             }
                             match numeric_literal.parse::<f64>() {
-                                Ok(n) => Some(TokenKind::Literal(n)),
+                                Ok(n) => Ok(TokenKind::Literal(n)),
                                 Err(_) => {
-                                    throw_lexer_syntax_error!(
-                                        "Standard Number",
-                                        "Malformed Number",
-                                        self.row,
-                                        self.col
-                                    );
+                                    Err(Diagnostic::new("malformed number", self.span_from(start)))
                                 }
                             }
                         }
                         invalid_token => throw_lexer_syntax_error!(
-                            "Valid Token",
-                            invalid_token,
-                            self.row,
-                            self.col
+                            "a valid token",
+                            format!("'{}'", invalid_token),
+                            self.span_from(start)
                         ),
                     }
                 }
             }
         } else {
-            Some(TokenKind::Eof)
+            Ok(TokenKind::Eof)
+        }
+    }
+
+    // The rest of a string literal after its opening quote. A string ends on
+    // the line it starts on, line breaks are written as `\n`.
+    fn string(&mut self, start: Span) -> Result<TokenKind, Diagnostic> {
+        let mut text = String::new();
+        loop {
+            let escape_start = self.here();
+            match self.look_ahead() {
+                Some('\n') | None => {
+                    return Err(
+                        Diagnostic::new("unterminated string", self.span_from(start))
+                            .with_expected("'\"'")
+                            .with_hint(
+                                "a string ends on the line it starts on, write a line break as \\n",
+                            ),
+                    );
+                }
+                Some(ch) => {
+                    self.advance();
+                    match ch {
+                        '"' => return Ok(TokenKind::String(text)),
+                        '\\' => text.push(self.escape(escape_start)?),
+                        ch => text.push(ch),
+                    }
+                }
+            }
+        }
+    }
+
+    // The character an escape stands for, `start` being where its `\` is.
+    fn escape(&mut self, start: Span) -> Result<char, Diagnostic> {
+        let escaped = match self.look_ahead() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some('u') => {
+                self.advance();
+                return self.unicode_escape(start);
+            }
+            _ => {
+                self.advance();
+                return Err(Diagnostic::new("unknown escape", self.span_from(start))
+                    .with_hint("the escapes are \\n, \\t, \\r, \\0, \\\\, \\\" and \\u{...}"));
+            }
+        };
+        self.advance();
+        Ok(escaped)
+    }
+
+    // `\u{...}` with up to six hex digits naming a Unicode scalar value.
+    fn unicode_escape(&mut self, start: Span) -> Result<char, Diagnostic> {
+        let mut digits = None;
+        if self.look_ahead() == Some('{') {
+            self.advance();
+            let hex = self.consume_while(|ch| ch.is_ascii_hexdigit());
+            if self.look_ahead() == Some('}') {
+                self.advance();
+                digits = Some(hex);
+            }
+        }
+        digits
+            .filter(|hex| (1..=6).contains(&hex.len()))
+            .and_then(|hex| u32::from_str_radix(&hex, 16).ok())
+            .and_then(char::from_u32)
+            .ok_or_else(|| {
+                Diagnostic::new("invalid unicode escape", self.span_from(start))
+                    .with_hint("write a code point in hex, like \\u{3bb} for λ")
+            })
+    }
+
+    // `double` if the next character is `next`, which is consumed with it,
+    // `single` otherwise. For operators like `==` next to `=`.
+    fn followed_by(&mut self, next: char, double: Operator, single: Operator) -> Operator {
+        if self.look_ahead() == Some(next) {
+            self.advance();
+            double
+        } else {
+            single
         }
     }
 
     fn consume_while(&mut self, mut predicate: impl FnMut(char) -> bool) -> String {
         let mut literal = String::new();
-        while let Some(ch) = self.consume() {
+        // For example, given ['x','y','z',' ','x1','y2','z3'], using `consume_while` with `!is_whitespace()`:
+        // The end result will include up to 'z' and the offset will point to the space " ".
+        // Only looking ahead keeps the offset moving forward, so row and col never need rewinding.
+        while let Some(ch) = self.look_ahead() {
             if !predicate(ch) {
-                // For example, given ['x','y','z',' ','x1','y2','z3'], using `consume_while` with `!is_whitespace()`:
-                // The end result will include up to 'z', but the offset will be set to 'x1'.
-                // According to the aforementioned prefix rule, `consume` must advance by +1 from the end result,
-                // i.e., the offset should point to the space " ".
-
-                // Normalizing the offset: when the literal actually ends, the offset would have been end character's offset +2.
-
-                self.offset = self.offset.saturating_sub(1);
                 break;
             }
             literal.push(ch);
+            self.advance();
         }
         literal
     }
 
     fn advance(&mut self) {
-        self.offset += 1
+        let Some(ch) = self.get_ch_at(self.offset) else {
+            return;
+        };
+        self.offset += 1;
+        match ch {
+            '\n' => {
+                self.row += 1;
+                self.col = 1;
+            }
+            // The '\r' of a CRLF ending is part of the line break, not a column.
+            '\r' if self.look_ahead() == Some('\n') => (),
+            _ => self.col += 1,
+        }
     }
-    
+
     #[allow(unused)]
     fn advance_by(&mut self, n: usize) {
-        self.offset += n;
+        for _ in 0..n {
+            self.advance();
+        }
     }
 
     fn get_ch_at(&mut self, n: usize) -> Option<char> {
-        if n < self.tokens.len() {
-            Some(self.tokens[self.offset])
-        } else {
-            None
+        self.tokens.get(n).map(|(_, ch)| *ch)
+    }
+
+    // Byte offset of the n-th character, the end of the source once past it.
+    fn byte_offset(&self, n: usize) -> usize {
+        self.tokens
+            .get(n)
+            .map_or(self.source_len, |(byte, _)| *byte)
+    }
+
+    // Empty span at the current offset, marks where a token starts.
+    fn here(&self) -> Span {
+        let byte = self.byte_offset(self.offset);
+        Span {
+            input: self.input,
+            ..Span::new(byte, byte, self.row, self.col)
+        }
+    }
+
+    // From a mark made with `here` up to the current offset.
+    fn span_from(&self, start: Span) -> Span {
+        Span {
+            end: self.byte_offset(self.offset),
+            ..start
         }
     }
 
@@ -272,3 +555,87 @@ impl Lexer {
         ch
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> String {
+        Lexer::new(source).get_tokens().unwrap_err().message.clone()
+    }
+
+    // Byte offsets count bytes, columns count characters, so 'λ' is two bytes
+    // wide and '𝑓' four, but both take one column like the tab does. A CRLF
+    // ending is a single line break.
+    #[test]
+    fn spans_count_bytes_and_characters() {
+        let spans = Lexer::new("(λx. 𝑓(x))\r\n\tab = 1")
+            .get_tokens()
+            .unwrap()
+            .into_iter()
+            .map(|token| {
+                let Span {
+                    start,
+                    end,
+                    line,
+                    col,
+                    ..
+                } = token.span;
+                (start, end, line, col)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            [
+                (0, 1, 1, 1),
+                (1, 3, 1, 2),
+                (3, 4, 1, 3),
+                (4, 5, 1, 4),
+                (6, 10, 1, 6),
+                (10, 11, 1, 7),
+                (11, 12, 1, 8),
+                (12, 13, 1, 9),
+                (13, 14, 1, 10),
+                (17, 19, 2, 2),
+                (20, 21, 2, 5),
+                (22, 23, 2, 7),
+                (23, 23, 2, 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn strings_replace_their_escapes() {
+        let tokens = Lexer::new(r#""a\"b\\\n\u{1F600}" x"#).get_tokens().unwrap();
+        assert_eq!(tokens[0].kind, TokenKind::String("a\"b\\\n😀".to_string()));
+        assert_eq!(tokens[1].kind, TokenKind::Identifier("x".to_string()));
+
+        assert_eq!(error("\"abc"), "unterminated string");
+        assert_eq!(error("\"abc\n\""), "unterminated string");
+        assert_eq!(error(r#""\q""#), "unknown escape");
+        assert_eq!(error(r#""\u{d800}""#), "invalid unicode escape");
+        assert_eq!(error(r#""\u41""#), "invalid unicode escape");
+    }
+
+    #[test]
+    fn at_opts_a_parameter_into_a_built_in() {
+        let kinds = Lexer::new("λ@print x. print")
+            .get_tokens()
+            .unwrap()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::Lamda,
+                TokenKind::Builtin("print".to_string()),
+                TokenKind::Identifier("x".to_string()),
+                TokenKind::Operator(Operator::Dot),
+                TokenKind::Identifier("print".to_string()),
+                TokenKind::Eof,
+            ]
+        );
+        assert_eq!(error("λ@ x. x"), "missing built-in name");
+    }
+}
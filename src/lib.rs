@@ -0,0 +1,62 @@
+// Lambda as a library, for running programs in-process instead of through the
+// `lamda` binary.
+//
+// `run_str` covers the common case. For more control, keep an Interpreter and
+// feed it source with `eval_expr_str`, a Host deciding where built-ins read and
+// write and a BuiltinRegistry deciding which built-ins exist. Everything else
+// is internal.
+
+mod abstractions;
+mod ast;
+mod builtins;
+mod bytecode;
+mod check;
+mod cli;
+mod error;
+mod format;
+mod host;
+mod interpreter;
+mod lexer;
+mod list;
+mod repl;
+mod resolver;
+mod vm;
+
+pub use builtins::BuiltinRegistry;
+pub use error::Diagnostic;
+pub use host::{Host, MemoryHost};
+pub use interpreter::{EvaluationValue, Interpreter, InterpreterConfig};
+
+use ast::Parser;
+use lexer::Lexer;
+
+// The `lamda` binary, a crate of its own, so it needs this one way in.
+#[doc(hidden)]
+pub use cli::main;
+
+// Runs a whole program with the standard built-ins and no limits, returning the
+// value of every statement. Pass `&mut host` to inspect the host afterwards.
+pub fn run_str<H: Host>(source: &str, host: H) -> Result<Vec<EvaluationValue>, Diagnostic> {
+    let mut program = Parser::parse_program(Lexer::new(source).get_tokens()?)?;
+    Interpreter::with_host(host, InterpreterConfig::default())
+        .with_builtins(BuiltinRegistry::standard())
+        .evaluate_program(&mut program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_str_leaves_the_host_with_the_caller() {
+        let mut host = MemoryHost::new("");
+        let results = run_str("x = 40\n(λ@print. x + 2) 0", &mut host).unwrap();
+        assert_eq!(host.output_string(), "42");
+        assert!(matches!(results[1], EvaluationValue::Literal(42.)));
+
+        let mut interpreter = Interpreter::with_host(MemoryHost::new(""), Default::default());
+        interpreter.eval_expr_str("double = λn. n * 2").unwrap();
+        let value = interpreter.eval_expr_str("(double) 21").unwrap();
+        assert!(matches!(value, EvaluationValue::Literal(42.)));
+    }
+}
@@ -0,0 +1,176 @@
+// The list value of both backends, `[1, 2, 3]` in the source.
+//
+// Lists are immutable and share their tails, so putting values in front of a
+// list and taking its tail don't copy anything.
+
+use std::{fmt, rc::Rc};
+
+pub struct List<T> {
+    head: Option<Rc<Node<T>>>,
+    len: usize,
+}
+
+struct Node<T> {
+    value: T,
+    next: Option<Rc<Node<T>>>,
+}
+
+impl<T> List<T> {
+    pub fn new() -> Self {
+        Self { head: None, len: 0 }
+    }
+
+    // `values` in order, followed by the elements of `rest`.
+    pub fn with_rest(values: Vec<T>, rest: &List<T>) -> Self {
+        values
+            .into_iter()
+            .rev()
+            .fold(rest.clone(), |list, value| list.prepend(value))
+    }
+
+    pub fn prepend(&self, value: T) -> Self {
+        Self {
+            head: Some(Rc::new(Node {
+                value,
+                next: self.head.clone(),
+            })),
+            len: self.len + 1,
+        }
+    }
+
+    pub fn head(&self) -> Option<&T> {
+        self.head.as_ref().map(|node| &node.value)
+    }
+
+    // Everything after the head, `None` for the empty list.
+    pub fn tail(&self) -> Option<Self> {
+        let node = self.head.as_ref()?;
+        Some(Self {
+            head: node.next.clone(),
+            len: self.len - 1,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let mut next = self.head.as_deref();
+        std::iter::from_fn(move || {
+            let node = next?;
+            next = node.next.as_deref();
+            Some(&node.value)
+        })
+    }
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for List<T> {
+    fn clone(&self) -> Self {
+        Self {
+            head: self.head.clone(),
+            len: self.len,
+        }
+    }
+}
+
+// Drops the nodes one after another, dropping them recursively would overflow
+// the stack on a long list.
+impl<T> Drop for List<T> {
+    fn drop(&mut self) {
+        let mut next = self.head.take();
+        while let Some(node) = next {
+            match Rc::try_unwrap(node) {
+                Ok(mut node) => next = node.next.take(),
+                // Still part of another list.
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for List<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+// `[1, 2, 3]`, the way it's written in the source.
+impl<T: fmt::Display> fmt::Display for List<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
+        for (index, value) in self.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", value)?;
+        }
+        write!(f, "]")
+    }
+}
+
+// What the list built-ins and `==` need from the values of a backend, so both
+// backends share them.
+pub trait Element: Clone {
+    fn number(number: f64) -> Self;
+    fn list(list: List<Self>) -> Self;
+    // `None` for values `==` can't compare, like abstractions.
+    fn comparable(&self) -> Option<Comparable<'_, Self>>;
+}
+
+pub enum Comparable<'a, T> {
+    Number(f64),
+    Text(&'a str),
+    List(&'a List<T>),
+}
+
+// Numbers, strings and lists of them are equal when they hold the same. `None`
+// when either is something else, those can't be compared.
+pub fn equal<T: Element>(lhs: &T, rhs: &T) -> Option<bool> {
+    use Comparable::{List, Number, Text};
+    match (lhs.comparable()?, rhs.comparable()?) {
+        (Number(l), Number(r)) => Some(l == r),
+        (Text(l), Text(r)) => Some(l == r),
+        (List(l), List(r)) => {
+            let mut same = l.len() == r.len();
+            for (l, r) in l.iter().zip(r.iter()) {
+                same &= equal(l, r)?;
+            }
+            Some(same)
+        }
+        _ => Some(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shares_tails_and_drops_long_lists() {
+        let list = List::with_rest(vec![1, 2], &List::new().prepend(3));
+        let tail = list.tail().unwrap();
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!((tail.len(), tail.head()), (2, Some(&2)));
+        assert_eq!(list.to_string(), "[1, 2, 3]");
+        drop(list);
+        assert_eq!(tail.to_string(), "[2, 3]");
+
+        // One native frame per node would overflow this stack.
+        let handle = std::thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(|| drop((0..1_000_000).fold(List::new(), |list, n| list.prepend(n))))
+            .unwrap();
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,436 @@
+// Interactive read-eval-print loop, started when no source file is given.
+//
+// Every line goes through the same Lexer -> Parser -> Interpreter pipeline as a file,
+// but a single Interpreter lives for the whole session so bindings persist across lines.
+
+use super::ast::{Parser, Program, Statement};
+use super::error::Diagnostic;
+use super::interpreter::{EvaluationValue, Interpreter, InterpreterConfig};
+use super::lexer::{Lexer, Operator, Token, TokenKind};
+
+use rustyline::{DefaultEditor, error::ReadlineError};
+use std::{env, fs, path::PathBuf, process::ExitCode};
+
+const REPL_FILE: &str = "<repl>";
+const PROMPT: &str = "λ> ";
+const CONTINUATION_PROMPT: &str = ".. ";
+
+const HELP: &str = "\
+:env          list global bindings
+:ast <expr>   print the syntax tree of <expr> without evaluating it
+:load <file>  evaluate a file into the current session
+:reset        drop every binding and start over
+:help         show this message
+:quit         leave the REPL (Ctrl-D works too)";
+
+pub fn run(config: InterpreterConfig) -> ExitCode {
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("Unable to start the REPL: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let history = history_path();
+    if let Some(history) = &history {
+        // A missing history file just means this is the first session.
+        let _ = editor.load_history(history);
+    }
+
+    println!("Lambda REPL, type :help for commands.");
+    let mut interpreter = Interpreter::with_config(config);
+    let mut inputs = Inputs::default();
+    let mut buffer = String::new();
+    loop {
+        let prompt = if buffer.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                // Ctrl-C discards the pending input, like most shells.
+                buffer.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                eprintln!("Unable to read input: {}", e);
+                break;
+            }
+        };
+
+        if buffer.is_empty() && line.trim_start().starts_with(':') {
+            let _ = editor.add_history_entry(line.as_str());
+            match run_command(line.trim(), &mut interpreter, &mut inputs) {
+                Command::Continue => continue,
+                Command::Quit => break,
+            }
+        }
+
+        buffer.push_str(&line);
+        buffer.push('\n');
+        let Some(tokens) = lex(&buffer, REPL_FILE, &inputs) else {
+            buffer.clear();
+            continue;
+        };
+        if is_incomplete(&tokens) {
+            continue;
+        }
+
+        let _ = editor.add_history_entry(buffer.trim_end());
+        if let Some(mut program) = parse(tokens, &buffer, REPL_FILE) {
+            inputs.add(REPL_FILE, &buffer);
+            evaluate(&mut interpreter, &mut program, &inputs, true);
+        }
+        buffer.clear();
+    }
+
+    if let Some(history) = &history {
+        let _ = editor.save_history(history);
+    }
+    ExitCode::SUCCESS
+}
+
+enum Command {
+    Continue,
+    Quit,
+}
+
+fn run_command(line: &str, interpreter: &mut Interpreter, inputs: &mut Inputs) -> Command {
+    let (command, argument) = match line.split_once(char::is_whitespace) {
+        Some((command, argument)) => (command, argument.trim()),
+        None => (line, ""),
+    };
+    match command {
+        ":env" => {
+            for (name, value) in interpreter.global_bindings() {
+                println!("{} = {}", name, value);
+            }
+        }
+        ":ast" => {
+            if let Some(program) = lex(argument, REPL_FILE, inputs)
+                .and_then(|tokens| parse(tokens, argument, REPL_FILE))
+            {
+                for statement in program.statements {
+                    if !matches!(statement, Statement::Eof) {
+                        println!("{:#?}", statement);
+                    }
+                }
+            }
+        }
+        ":load" => {
+            if argument.is_empty() {
+                eprintln!("Usage: :load <file>");
+                return Command::Continue;
+            }
+            match fs::read_to_string(argument) {
+                Ok(source) => {
+                    if let Some(mut program) = lex(&source, argument, inputs)
+                        .and_then(|tokens| parse(tokens, &source, argument))
+                    {
+                        inputs.add(argument, &source);
+                        evaluate(interpreter, &mut program, inputs, false);
+                    }
+                }
+                Err(e) => eprintln!("Unable to read {}: {}", argument, e),
+            }
+        }
+        ":reset" => interpreter.reset(),
+        ":help" => println!("{}", HELP),
+        ":quit" | ":q" => return Command::Quit,
+        unknown => eprintln!("Unknown command {}, type :help for commands.", unknown),
+    }
+    Command::Continue
+}
+
+// Every input evaluated so far, `:load`ed files included. A closure defined in
+// one of them can fail on a later line, its spans are tagged with the input
+// they are in so the error is drawn against that one.
+#[derive(Default)]
+struct Inputs {
+    // File name and source.
+    inputs: Vec<(String, String)>,
+}
+
+impl Inputs {
+    // What the next input added will be tagged with.
+    fn next(&self) -> usize {
+        self.inputs.len()
+    }
+
+    fn add(&mut self, file: &str, source: &str) {
+        self.inputs.push((file.to_string(), source.to_string()));
+    }
+
+    fn render(&self, diagnostic: Diagnostic) -> String {
+        match self.inputs.get(diagnostic.span.input) {
+            Some((file, source)) => diagnostic.with_file(file).render(source),
+            None => diagnostic.with_file(REPL_FILE).render(""),
+        }
+    }
+}
+
+// Lexes `source` as the next input, so it has to be added before its
+// program runs.
+fn lex(source: &str, file: &str, inputs: &Inputs) -> Option<Vec<Token>> {
+    match Lexer::new(source).with_input(inputs.next()).get_tokens() {
+        Ok(tokens) => Some(tokens),
+        Err(diagnostic) => {
+            eprint!("{}", diagnostic.with_file(file).render(source));
+            None
+        }
+    }
+}
+
+fn parse(tokens: Vec<Token>, source: &str, file: &str) -> Option<Program> {
+    let (program, diagnostics) = Parser::parse_recovering(tokens);
+    if diagnostics.is_empty() {
+        return Some(program);
+    }
+    for diagnostic in diagnostics {
+        eprint!("{}", diagnostic.with_file(file).render(source));
+    }
+    None
+}
+
+fn evaluate(interpreter: &mut Interpreter, program: &mut Program, inputs: &Inputs, echo: bool) {
+    let results = interpreter.evaluate_program(program);
+    // Built-ins such as λ@print don't end their output with a newline.
+    if interpreter.host_mut().take_line_open() {
+        println!();
+    }
+    match results {
+        Ok(results) if echo => {
+            for (statement, result) in program.statements.iter().zip(results) {
+                if matches!(statement, Statement::ExpressionStmt(_))
+                    && !matches!(result, EvaluationValue::Unit)
+                {
+                    println!("=> {}", result);
+                }
+            }
+        }
+        Ok(_) => (),
+        Err(diagnostic) => eprint!("{}", inputs.render(diagnostic)),
+    }
+}
+
+// The input keeps going on the next line while parentheses or brackets are
+// open, a `let` has no `in` yet, an `if`
+// has no `else` yet, or it ends where more has to follow, e.g. `f = λx.`,
+// `λx. x +` or an application still waiting for its argument,
+// `(λ@print. print)`. `(a + b)`, `(a)` and `((a))` only group, so they don't
+// wait, the end of the input closes them like it closes a statement.
+fn is_incomplete(tokens: &[Token]) -> bool {
+    let mut open_parens: Vec<Paren> = Vec::new();
+    let mut closed_application = false;
+    let mut waits_for_argument = false;
+    let mut open_ifs = 0usize;
+    let mut open_brackets = 0usize;
+    let mut open_lets = 0usize;
+    let mut last: Option<&TokenKind> = None;
+    for token in tokens.iter().map(|token| &token.kind) {
+        if matches!(token, TokenKind::Comment(_) | TokenKind::Eof) {
+            continue;
+        }
+        if let Some(paren) = open_parens.last_mut() {
+            if closed_application && starts_argument(token) {
+                // What follows is the argument, up to the closing parenthesis.
+                paren.open_ended = true;
+            }
+            match token {
+                TokenKind::Lamda | TokenKind::If | TokenKind::Let => paren.open_ended = true,
+                token if is_operator(token) && !paren.open_ended => paren.groups = true,
+                _ => (),
+            }
+        }
+        closed_application = false;
+        waits_for_argument = false;
+        match token {
+            TokenKind::If => open_ifs += 1,
+            TokenKind::Else => open_ifs = open_ifs.saturating_sub(1),
+            TokenKind::Let => open_lets += 1,
+            TokenKind::In => open_lets = open_lets.saturating_sub(1),
+            TokenKind::Operator(Operator::LeftBracket) => open_brackets += 1,
+            TokenKind::Operator(Operator::RightBracket) => {
+                open_brackets = open_brackets.saturating_sub(1)
+            }
+            TokenKind::Operator(Operator::LeftParen) => open_parens.push(Paren {
+                application: !matches!(last, Some(&TokenKind::Recursion)),
+                groups: false,
+                open_ended: false,
+            }),
+            TokenKind::Operator(Operator::RightParen) => {
+                if let Some(paren) = open_parens.pop() {
+                    closed_application = paren.application && !paren.groups;
+                    // Only a parenthesis holding an abstraction, an `if`, a
+                    // `let` or an application can evaluate to a function.
+                    waits_for_argument = closed_application && paren.open_ended;
+                }
+            }
+            _ => (),
+        }
+        last = Some(token);
+    }
+    if !open_parens.is_empty()
+        || waits_for_argument
+        || open_ifs > 0
+        || open_brackets > 0
+        || open_lets > 0
+    {
+        return true;
+    }
+    matches!(
+        last,
+        Some(TokenKind::Lamda)
+            | Some(TokenKind::Recursion)
+            | Some(TokenKind::If)
+            | Some(TokenKind::Then)
+            | Some(TokenKind::Else)
+            | Some(TokenKind::In)
+            | Some(TokenKind::Operator(Operator::Dot | Operator::Equal))
+    ) || last.is_some_and(is_operator)
+}
+
+// An open parenthesis in the input typed so far.
+struct Paren {
+    // false for 𝑓(...)
+    application: bool,
+    // Set once an operator shows up directly inside, as in `(a + b)`.
+    groups: bool,
+    // Set once the rest is an abstraction body or an argument, whose
+    // operators don't make the parenthesis a group.
+    open_ended: bool,
+}
+
+fn is_operator(token: &TokenKind) -> bool {
+    matches!(
+        token,
+        TokenKind::Operator(
+            Operator::Plus
+                | Operator::Minus
+                | Operator::Asterisk
+                | Operator::Slash
+                | Operator::Percent
+                | Operator::Div
+                | Operator::DoubleAsterisk
+                | Operator::ShiftLeft
+                | Operator::ShiftRight
+                | Operator::Caret
+                | Operator::BitAnd
+                | Operator::BitOr
+                | Operator::EqualEqual
+                | Operator::NotEqual
+                | Operator::Less
+                | Operator::LessEqual
+                | Operator::Greater
+                | Operator::GreaterEqual
+                | Operator::And
+                | Operator::Or
+                | Operator::Not
+        )
+    )
+}
+
+fn starts_argument(token: &TokenKind) -> bool {
+    matches!(
+        token,
+        TokenKind::Identifier(_)
+            | TokenKind::Literal(_)
+            | TokenKind::String(_)
+            | TokenKind::Lamda
+            | TokenKind::Recursion
+            | TokenKind::If
+            | TokenKind::Let
+            | TokenKind::Operator(Operator::LeftParen | Operator::LeftBracket | Operator::Not)
+    )
+}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".lamda_history"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::BuiltinRegistry;
+    use crate::host::MemoryHost;
+
+    fn incomplete(source: &str) -> bool {
+        is_incomplete(&Lexer::new(source).get_tokens().unwrap())
+    }
+
+    #[test]
+    fn waits_for_whatever_has_to_follow() {
+        for source in [
+            "(λx. x",
+            "f = λx.",
+            "f =",
+            "x = 1 +",
+            "(λ@print. print)",
+            "if x then 1",
+            "let a = 1",
+            "let a = 1 in",
+            "[1, 2",
+            "(λ@print. 𝑓(",
+        ] {
+            assert!(incomplete(source), "{}", source);
+        }
+        for source in [
+            "(λx. x) 1",
+            "f = λx. x",
+            "x = 1 + 2 // a comment +",
+            "(λ@print. print)\n10",
+            "if x then 1 else 2",
+            "let a = 1 in a",
+            "[1, 2]",
+            "(a)",
+            "c = (a)",
+            "((a))",
+            "x = (a) + 1",
+            "",
+        ] {
+            assert!(!incomplete(source), "{}", source);
+        }
+    }
+
+    #[test]
+    fn waits_for_an_argument_only_after_a_function() {
+        for source in [
+            "(λ@print. print)",
+            "f = (λx. λy. x)",
+            "(if a then λx. x else λx. 0)",
+            "(let g = λx. x in g)",
+            "((λx. λy. x) 1)",
+        ] {
+            assert!(incomplete(source), "{}", source);
+        }
+        for source in ["(a)", "c = (a)", "((a))", "(a)\n(b)", "(λx. x) (a)"] {
+            assert!(!incomplete(source), "{}", source);
+        }
+    }
+
+    #[test]
+    fn errors_are_drawn_against_the_input_they_come_from() {
+        let mut interpreter =
+            Interpreter::with_host(MemoryHost::new(""), InterpreterConfig::default())
+                .with_builtins(BuiltinRegistry::standard());
+        let mut inputs = Inputs::default();
+        let mut error = None;
+        // The error is raised on the second line, by the first one. Its
+        // offsets fall inside a 'λ' of the second line, which used to panic.
+        for line in ["f = λx. 1 + \"a\"\n", "(f)  \"λλλλλλλλ\"\n"] {
+            let tokens = lex(line, REPL_FILE, &inputs).unwrap();
+            let mut program = parse(tokens, line, REPL_FILE).unwrap();
+            inputs.add(REPL_FILE, line);
+            error = interpreter.evaluate_program(&mut program).err();
+        }
+        let rendered = inputs.render(error.unwrap());
+        assert!(
+            rendered.contains("1 | f = λx. 1 + \"a\"\n  |             ^^^ found \"a\"\n"),
+            "{}",
+            rendered
+        );
+    }
+}
@@ -0,0 +1,216 @@
+// Resolves every identifier before anything runs.
+//
+// A parameter of an enclosing abstraction becomes a depth, 0 being the innermost
+// abstraction, and everything else a global slot. Names bound nowhere, and
+// `λ@name.` with no built-in registered as `name`, are reported here instead of
+// whenever evaluation happens to reach them.
+
+use super::ast::{Binding, Expr, ExprKind, Program, Statement};
+use super::builtins::BuiltinRegistry;
+use super::error::{Diagnostic, Span};
+
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+// Globals keep their slot for as long as the resolver lives, so a REPL session
+// or a later `evaluate_program` call sees the bindings made before it.
+#[derive(Debug, Default)]
+pub struct Resolver {
+    slots: HashMap<String, usize>,
+    names: Vec<String>,
+    // Globals bound by a statement that has been resolved.
+    defined: HashSet<String>,
+}
+
+// What an identifier can refer to at the point it's resolved.
+struct Context<'a> {
+    // Parameters of the enclosing abstractions, innermost last.
+    params: Vec<String>,
+    // Set inside abstraction bodies that aren't applied on the spot, they only
+    // run later, so they may use globals bound further down.
+    deferred: bool,
+    // Globals the program binds, with where.
+    bound_later: &'a HashMap<String, Span>,
+    // The global the statement being resolved binds.
+    binding: Option<&'a str>,
+    builtins: &'a BuiltinRegistry,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn global_name(&self, slot: usize) -> &str {
+        &self.names[slot]
+    }
+
+    pub fn global_count(&self) -> usize {
+        self.names.len()
+    }
+
+    // The slot of a global bound by a resolved statement.
+    pub fn slot(&self, name: &str) -> Option<usize> {
+        self.slots.get(name).copied()
+    }
+
+    // Annotates every identifier in `program`, returning one diagnostic per
+    // unbound name or unknown built-in. When there are any, none of the
+    // program's bindings count as defined afterwards.
+    pub fn resolve_program(
+        &mut self,
+        program: &mut Program,
+        builtins: &BuiltinRegistry,
+    ) -> Vec<Diagnostic> {
+        let mut bound_later = HashMap::new();
+        for statement in &program.statements {
+            if let Statement::Binding { name, value } = statement {
+                bound_later.entry(name.clone()).or_insert(value.span);
+            }
+        }
+
+        let defined_before = self.defined.clone();
+        let mut unbound = Vec::new();
+        for statement in &mut program.statements {
+            let (expr, name) = match statement {
+                Statement::Binding { name, value } => (value, Some(name)),
+                Statement::ExpressionStmt(expr) => (expr, None),
+                Statement::Comment { .. } | Statement::Eof => continue,
+            };
+            let mut context = Context {
+                params: Vec::new(),
+                deferred: false,
+                bound_later: &bound_later,
+                binding: name.as_deref().map(String::as_str),
+                builtins,
+            };
+            self.resolve_expr(expr, &mut context, &mut unbound);
+            if let Some(name) = name {
+                self.slot_for(name);
+                self.defined.insert(name.clone());
+            }
+        }
+        if !unbound.is_empty() {
+            self.defined = defined_before;
+        }
+        unbound
+    }
+
+    fn resolve_expr(
+        &mut self,
+        expr: &mut Expr,
+        context: &mut Context,
+        unbound: &mut Vec<Diagnostic>,
+    ) {
+        match &mut expr.kind {
+            ExprKind::Identifier { name, binding } => {
+                if let Some(position) = context.params.iter().rposition(|param| param == name) {
+                    *binding = Binding::Local {
+                        depth: context.params.len() - 1 - position,
+                    };
+                } else if self.defined.contains(name.as_str())
+                    || (context.deferred && context.bound_later.contains_key(name.as_str()))
+                {
+                    *binding = Binding::Global {
+                        index: self.slot_for(name),
+                    };
+                } else {
+                    let mut diagnostic =
+                        Diagnostic::new(format!("unbound binding '{}'", name), expr.span);
+                    if context.binding == Some(name.as_str()) {
+                        diagnostic = diagnostic.with_hint(format!(
+                            "'{}' is what this statement binds, only an abstraction in its value can refer to it",
+                            name
+                        ));
+                    } else if let Some(span) = context.bound_later.get(name.as_str()) {
+                        diagnostic = diagnostic.with_hint(format!(
+                            "'{}' is only bound further down, at line {}",
+                            name, span.line
+                        ));
+                    }
+                    unbound.push(diagnostic);
+                }
+            }
+            ExprKind::Literal(_) | ExprKind::String(_) => (),
+            ExprKind::Abstraction { .. } => self.resolve_abstraction(expr, true, context, unbound),
+            // Nothing shares the tree while it's being resolved, so these don't
+            // copy anything.
+            ExprKind::Recursion(arg) => self.resolve_expr(Rc::make_mut(arg), context, unbound),
+            ExprKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.resolve_expr(condition, context, unbound);
+                self.resolve_expr(then_branch, context, unbound);
+                self.resolve_expr(else_branch, context, unbound);
+            }
+            ExprKind::Application { func, arg } => {
+                // `(λx. body) arg` runs the body right away.
+                self.resolve_abstraction(func, context.deferred, context, unbound);
+                self.resolve_expr(arg, context, unbound);
+            }
+            ExprKind::BinaryOperation { lhs, rhs, .. } => {
+                self.resolve_expr(lhs, context, unbound);
+                self.resolve_expr(rhs, context, unbound);
+            }
+            ExprKind::UnaryOperation { operand, .. } => {
+                self.resolve_expr(operand, context, unbound)
+            }
+            ExprKind::List { elements, rest } => {
+                for element in elements.iter_mut().chain(rest.as_deref_mut()) {
+                    self.resolve_expr(element, context, unbound);
+                }
+            }
+            // Bound in a scope of its own, like a parameter.
+            ExprKind::Let { name, value, body } => {
+                self.resolve_expr(value, context, unbound);
+                context.params.push(name.clone());
+                self.resolve_expr(body, context, unbound);
+                context.params.pop();
+            }
+        }
+    }
+
+    // Resolves the body of `expr` with its parameter in scope, anything other
+    // than an abstraction is resolved as usual.
+    fn resolve_abstraction(
+        &mut self,
+        expr: &mut Expr,
+        deferred: bool,
+        context: &mut Context,
+        unbound: &mut Vec<Diagnostic>,
+    ) {
+        let ExprKind::Abstraction {
+            param,
+            builtin,
+            body,
+        } = &mut expr.kind
+        else {
+            return self.resolve_expr(expr, context, unbound);
+        };
+        if *builtin && context.builtins.get(param).is_none() {
+            unbound.push(
+                Diagnostic::new(format!("unknown built-in '@{}'", param), expr.span).with_hint(
+                    format!("the built-ins are {}", context.builtins.names().join(", ")),
+                ),
+            );
+        }
+        let outer_deferred = context.deferred;
+        context.params.push(param.to_string());
+        context.deferred = deferred;
+        self.resolve_expr(Rc::make_mut(body), context, unbound);
+        context.deferred = outer_deferred;
+        context.params.pop();
+    }
+
+    fn slot_for(&mut self, name: &str) -> usize {
+        if let Some(slot) = self.slots.get(name) {
+            return *slot;
+        }
+        let slot = self.names.len();
+        self.slots.insert(name.to_string(), slot);
+        self.names.push(name.to_string());
+        slot
+    }
+}
@@ -0,0 +1,772 @@
+// Stack machine running the bytecode from bytecode.rs, selected with `--vm`.
+//
+// It follows the tree-walking Interpreter step for step, including HALT and 𝑓,
+// but keeps its own frames instead of recursing, so deep programs don't grow
+// the Rust stack either.
+
+use super::ast::{Program, Statement};
+use super::builtins::{Argument, BuiltinRegistry};
+use super::bytecode::{Capture, Compiler, Function, Op};
+use super::error::{Diagnostic, Span, TraceFrame};
+use super::host::{Host, TerminalHost};
+use super::interpreter::{EvaluationValue, InterpreterConfig};
+use super::list::{Comparable, Element, List, equal};
+use super::resolver::Resolver;
+
+use std::{fmt, rc::Rc, time::Instant};
+
+type Result<T> = std::result::Result<T, Diagnostic>;
+
+// Applications nested at the same time when `max_depth` isn't set. Frames live
+// on the heap, this only keeps a runaway recursion from taking all of it, as
+// the native stack does for the tree-walking interpreter.
+pub const DEFAULT_MAX_FRAMES: usize = 1 << 20;
+
+// The VM's counterpart of EvaluationValue, printed the same way.
+#[derive(Debug, Clone)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Value {
+    Literal(f64),
+    String(Rc<str>),
+    List(List<Value>),
+    Closure(Rc<Closure>),
+    Unit,
+    // The argument of 𝑓, not evaluated yet.
+    Recursion(Rc<Closure>),
+    HALT,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Literal(literal) => write!(f, "{}", literal),
+            Value::String(text) => write!(f, "{:?}", text),
+            Value::List(list) => write!(f, "{}", list),
+            Value::Closure(closure) => {
+                let at = if closure.function.builtin.is_some() {
+                    "@"
+                } else {
+                    ""
+                };
+                let param = closure.function.param.as_deref().unwrap_or("");
+                write!(f, "<λ{}{}>", at, param)
+            }
+            Value::Unit => write!(f, "()"),
+            Value::Recursion(_) => write!(f, "<𝑓>"),
+            Value::HALT => write!(f, "HALT"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Closure {
+    function: Rc<Function>,
+    captures: Vec<Value>,
+}
+
+enum FrameKind {
+    Statement,
+    Application {
+        span: Span,
+        recursion_depth: usize,
+        // Set once the 𝑓 argument of this iteration has been evaluated.
+        next_arg: Option<Value>,
+    },
+    // Evaluating the argument of 𝑓 for the application below it.
+    Recursion,
+}
+
+struct Frame {
+    closure: Rc<Closure>,
+    ip: usize,
+    // Stack height when the frame was entered.
+    base: usize,
+    // Where the values of the `let`s the frame is inside of start in `locals`.
+    locals: usize,
+    param: Value,
+    kind: FrameKind,
+}
+
+pub struct Vm<H: Host = TerminalHost> {
+    host: H,
+    builtins: BuiltinRegistry,
+    resolver: Resolver,
+    compiler: Compiler,
+    globals: Vec<Option<Value>>,
+    stack: Vec<Value>,
+    // The values of the `let`s every frame is inside of, innermost last. Kept
+    // apart from `stack` as operands can be pushed on top of them.
+    locals: Vec<Value>,
+    frames: Vec<Frame>,
+    // Application frames among `frames`, for the depth limit.
+    depth: usize,
+    config: InterpreterConfig,
+    // Whether there is a step or time limit to check after every op.
+    limited: bool,
+    steps: u64,
+    deadline: Option<Instant>,
+}
+
+impl Vm {
+    // Runs in the terminal with the standard built-ins.
+    pub fn with_config(config: InterpreterConfig) -> Self {
+        Self::with_host(TerminalHost::new(), config).with_builtins(BuiltinRegistry::standard())
+    }
+}
+
+impl<H: Host> Vm<H> {
+    // Starts without any built-ins, they are opted into with `with_builtins`.
+    pub fn with_host(host: H, config: InterpreterConfig) -> Self {
+        Vm {
+            host,
+            builtins: BuiltinRegistry::new(),
+            resolver: Resolver::new(),
+            compiler: Compiler::new(),
+            globals: Vec::new(),
+            stack: Vec::new(),
+            locals: Vec::new(),
+            frames: Vec::new(),
+            depth: 0,
+            config,
+            limited: false,
+            steps: 0,
+            deadline: None,
+        }
+    }
+
+    // Built-ins are resolved while compiling, so this affects programs
+    // evaluated from then on.
+    pub fn with_builtins(mut self, builtins: BuiltinRegistry) -> Self {
+        self.builtins = builtins;
+        self
+    }
+
+    pub fn host_mut(&mut self) -> &mut H {
+        &mut self.host
+    }
+
+    // Resolves `program` in place, so its identifiers end up annotated for this
+    // evaluator. Unbound names are reported before any statement runs, the
+    // first one becomes the error.
+    pub fn evaluate_program(&mut self, program: &mut Program) -> Result<Vec<Value>> {
+        if let Some(unbound) = self
+            .resolver
+            .resolve_program(program, &self.builtins)
+            .into_iter()
+            .next()
+        {
+            return Err(unbound);
+        }
+        self.globals.resize(self.resolver.global_count(), None);
+        let statements = program
+            .statements
+            .iter()
+            .map(|statement| {
+                let slot = match statement {
+                    Statement::Binding { name, .. } => self.resolver.slot(name),
+                    _ => None,
+                };
+                self.compiler
+                    .compile_statement(statement, slot, &self.builtins)
+            })
+            .collect::<Vec<_>>();
+
+        let mut results = Vec::new();
+        self.steps = 0;
+        self.deadline = self.config.timeout.map(|timeout| Instant::now() + timeout);
+        self.limited = self.config.max_steps.is_some() || self.deadline.is_some();
+        for statement in statements {
+            let result = match statement {
+                Some(function) => self.run(function)?,
+                None => Value::Unit,
+            };
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    // Runtime error at `span`, with the application chain that led there. The
+    // chain is only put together here, from the frames, so applying an
+    // abstraction costs nothing extra for it.
+    fn error(&self, message: impl Into<String>, span: Span) -> Diagnostic {
+        let trace = self
+            .frames
+            .iter()
+            .filter_map(|frame| match frame.kind {
+                FrameKind::Application {
+                    span,
+                    recursion_depth,
+                    ..
+                } => Some(TraceFrame {
+                    name: frame.closure.function.param.clone().unwrap_or_default(),
+                    builtin: frame.closure.function.builtin.is_some(),
+                    span,
+                    recursion_depth,
+                }),
+                _ => None,
+            })
+            .collect();
+        Diagnostic::new(message, span).with_trace(trace)
+    }
+
+    #[inline]
+    fn tick(&mut self, span: Span) -> Result<()> {
+        if !self.limited {
+            return Ok(());
+        }
+        self.steps += 1;
+        match self.config.exhausted(self.steps, self.deadline) {
+            Some((message, hint)) => Err(self.error(message, span).with_hint(hint)),
+            None => Ok(()),
+        }
+    }
+
+    fn run(&mut self, function: Rc<Function>) -> Result<Value> {
+        // A failed statement leaves its frames behind, start clean.
+        self.stack.clear();
+        self.locals.clear();
+        self.frames.clear();
+        self.depth = 0;
+        self.frames.push(Frame {
+            closure: Rc::new(Closure {
+                function,
+                captures: Vec::new(),
+            }),
+            ip: 0,
+            base: 0,
+            locals: 0,
+            param: Value::Unit,
+            kind: FrameKind::Statement,
+        });
+
+        // The running frame's function and position are kept out here, they
+        // only go back into the frame when another one is entered or left.
+        let (mut function, mut ip) = self.resume();
+        loop {
+            let op = function.code[ip];
+            ip += 1;
+            if self.limited {
+                self.tick(function.spans[ip - 1])?;
+            }
+
+            match op {
+                Op::Number(number) => self.stack.push(Value::Literal(number)),
+                Op::String(index) => {
+                    let text = Rc::clone(&function.strings[index as usize]);
+                    self.stack.push(Value::String(text));
+                }
+                Op::Param => {
+                    let value = self.frame().param.clone();
+                    self.stack.push(value);
+                }
+                Op::Local(index) => {
+                    let value = self.locals[self.frame().locals + index as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::Capture(index) => {
+                    let value = self.frame().closure.captures[index as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::Global(slot) => match &self.globals[slot as usize] {
+                    Some(value) => self.stack.push(value.clone()),
+                    None => {
+                        return Err(self.error(
+                            format!(
+                                "unbound binding '{}'",
+                                self.resolver.global_name(slot as usize)
+                            ),
+                            function.spans[ip - 1],
+                        ));
+                    }
+                },
+                Op::SetGlobal(slot) => {
+                    self.globals[slot as usize] = Some(self.peek().clone());
+                }
+                Op::Closure(index) => {
+                    let closure = self.closure(&function.functions[index as usize]);
+                    self.stack.push(Value::Closure(closure));
+                }
+                Op::Recursion(index) => {
+                    let closure = self.closure(&function.functions[index as usize]);
+                    self.stack.push(Value::Recursion(closure));
+                }
+                Op::List(count, has_rest) => {
+                    let rest = match has_rest {
+                        false => List::new(),
+                        true => match self.pop() {
+                            Value::List(list) => list,
+                            found => {
+                                return Err(self
+                                    .error("expected a list after '..'", function.spans[ip - 2])
+                                    .with_found(found));
+                            }
+                        },
+                    };
+                    let values = self.stack.split_off(self.stack.len() - count as usize);
+                    self.stack.push(Value::List(List::with_rest(values, &rest)));
+                }
+                Op::Binary(op, lhs_end) => {
+                    if let [.., Value::Literal(l), Value::Literal(r)] = self.stack[..] {
+                        self.stack.pop();
+                        *self.stack.last_mut().expect("the left operand") =
+                            Value::Literal(op.apply(l, r));
+                        continue;
+                    }
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    if let (Value::Literal(l), Value::Literal(r)) = (&lhs, &rhs) {
+                        self.stack.push(Value::Literal(op.apply(*l, *r)));
+                        continue;
+                    }
+                    if let Some(equal) = equal(&lhs, &rhs)
+                        && let Some(result) = op.apply_equality(equal)
+                    {
+                        self.stack.push(Value::Literal(result));
+                        continue;
+                    }
+                    let span = match lhs {
+                        Value::Literal(_) => function.spans[ip - 2],
+                        _ => function.spans[lhs_end as usize],
+                    };
+                    let found = match lhs {
+                        Value::Literal(_) => rhs,
+                        _ => lhs,
+                    };
+                    return Err(self
+                        .error("expected numeric literal for binary operations", span)
+                        .with_found(found));
+                }
+                Op::Unary(op) => match self.pop() {
+                    Value::Literal(number) => self.stack.push(Value::Literal(op.apply(number))),
+                    found => {
+                        return Err(self
+                            .error(
+                                "expected numeric literal for unary operations",
+                                function.spans[ip - 2],
+                            )
+                            .with_found(found));
+                    }
+                },
+                Op::Apply(func_end) => {
+                    let arg = self.pop();
+                    match self.pop() {
+                        Value::Closure(closure) => {
+                            self.suspend(ip);
+                            self.enter(closure, arg, function.spans[ip - 1])?;
+                            (function, ip) = self.resume();
+                        }
+                        Value::Literal(literal) => self.stack.push(Value::Literal(literal)),
+                        Value::Unit => self.stack.push(Value::Unit),
+                        Value::HALT => self.stack.push(Value::HALT),
+                        found => {
+                            return Err(self
+                                .error(
+                                    "unexpected evaluation value",
+                                    function.spans[func_end as usize],
+                                )
+                                .with_found(found));
+                        }
+                    }
+                }
+                Op::Branch(else_start, end) => match self.pop() {
+                    Value::Literal(0.) => ip = else_start as usize,
+                    Value::Literal(_) => (),
+                    Value::HALT => {
+                        self.stack.push(Value::HALT);
+                        ip = end as usize;
+                    }
+                    found => {
+                        return Err(self
+                            .error(
+                                "expected numeric literal for the condition",
+                                function.spans[ip - 1],
+                            )
+                            .with_found(found));
+                    }
+                },
+                Op::Jump(target) => ip = target as usize,
+                Op::Let => {
+                    let value = self.pop();
+                    self.locals.push(value);
+                }
+                Op::EndLet => {
+                    self.locals.pop();
+                }
+                Op::Next => {
+                    if !matches!(self.peek(), Value::Literal(_) | Value::HALT) {
+                        let found = self.pop();
+                        return Err(self
+                            .error(
+                                "recursion (𝑓) only takes numeric value",
+                                function.spans[ip - 1],
+                            )
+                            .with_found(found));
+                    }
+                    let next = self.peek().clone();
+                    if let FrameKind::Application { next_arg, .. } =
+                        &mut self.frames.last_mut().expect("a running frame").kind
+                    {
+                        *next_arg = Some(next);
+                    }
+                }
+                Op::End => {
+                    if let Value::Recursion(_) = self.peek() {
+                        let Value::Recursion(closure) = self.pop() else {
+                            unreachable!()
+                        };
+                        // Back to this End with the argument's value.
+                        self.suspend(ip - 1);
+                        // Its captures already hold the parameter it needs.
+                        self.frames.push(Frame {
+                            closure,
+                            ip: 0,
+                            base: self.stack.len(),
+                            locals: self.locals.len(),
+                            param: Value::Unit,
+                            kind: FrameKind::Recursion,
+                        });
+                        (function, ip) = self.resume();
+                        continue;
+                    }
+                    if !matches!(self.peek(), Value::HALT) {
+                        if function.builtin.is_some() {
+                            self.call_builtin()?;
+                        }
+                        if self.next_iteration() {
+                            ip = 0;
+                            continue;
+                        }
+                    }
+                    self.ret()?;
+                    (function, ip) = self.resume();
+                }
+                Op::Return => {
+                    if let Some(result) = self.ret()? {
+                        return Ok(result);
+                    }
+                    (function, ip) = self.resume();
+                }
+            }
+        }
+    }
+
+    // Leaves the running frame at `ip`, to go on there once it's resumed.
+    fn suspend(&mut self, ip: usize) {
+        self.frames.last_mut().expect("a running frame").ip = ip;
+    }
+
+    // The function and position of the frame on top.
+    fn resume(&self) -> (Rc<Function>, usize) {
+        let frame = self.frame();
+        (Rc::clone(&frame.closure.function), frame.ip)
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames.last().expect("a running frame")
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("a value on the stack")
+    }
+
+    fn peek(&self) -> &Value {
+        self.stack.last().expect("a value on the stack")
+    }
+
+    fn closure(&self, function: &Rc<Function>) -> Rc<Closure> {
+        let frame = self.frame();
+        let captures = function
+            .captures
+            .iter()
+            .map(|capture| match capture {
+                Capture::Param => frame.param.clone(),
+                Capture::Local(index) => self.locals[frame.locals + *index as usize].clone(),
+                Capture::Captured(index) => frame.closure.captures[*index as usize].clone(),
+            })
+            .collect();
+        Rc::new(Closure {
+            function: Rc::clone(function),
+            captures,
+        })
+    }
+
+    fn enter(&mut self, closure: Rc<Closure>, arg: Value, span: Span) -> Result<()> {
+        match self.config.max_depth {
+            Some(max_depth) if self.depth >= max_depth => {
+                return Err(self
+                    .error(
+                        format!(
+                            "fuel exhausted, applications nested deeper than {}",
+                            max_depth
+                        ),
+                        span,
+                    )
+                    .with_hint("the depth limit is set with --max-depth"));
+            }
+            None if self.depth >= DEFAULT_MAX_FRAMES => {
+                return Err(self
+                    .error("fuel exhausted, applications nested too deeply", span)
+                    .with_hint(format!(
+                        "the VM nests at most {} applications unless --max-depth says otherwise",
+                        DEFAULT_MAX_FRAMES
+                    )));
+            }
+            _ => (),
+        }
+        self.depth += 1;
+        self.frames.push(Frame {
+            closure,
+            ip: 0,
+            base: self.stack.len(),
+            locals: self.locals.len(),
+            param: arg,
+            kind: FrameKind::Application {
+                span,
+                recursion_depth: 0,
+                next_arg: None,
+            },
+        });
+        Ok(())
+    }
+
+    // Leaves the current frame, passing the value on top of the stack to the
+    // frame below. Returns the result once the statement itself is done.
+    fn ret(&mut self) -> Result<Option<Value>> {
+        let result = self.pop();
+        let frame = self.frames.pop().expect("a running frame");
+        self.stack.truncate(frame.base);
+        // A HALT can return from inside `let`s.
+        self.locals.truncate(frame.locals);
+        match frame.kind {
+            FrameKind::Statement => return Ok(Some(result)),
+            FrameKind::Application { .. } => self.depth -= 1,
+            FrameKind::Recursion => {
+                // Only a single depth and a valid literal or halt signal is allowed.
+                if !matches!(result, Value::Literal(_) | Value::HALT) {
+                    return Err(self
+                        .error(
+                            "recursion (𝑓) only takes numeric value",
+                            frame.closure.function.span,
+                        )
+                        .with_found(result));
+                }
+                if let Some(Frame {
+                    kind: FrameKind::Application { next_arg, .. },
+                    ..
+                }) = self.frames.last_mut()
+                {
+                    *next_arg = Some(result.clone());
+                }
+            }
+        }
+        self.stack.push(result);
+        Ok(None)
+    }
+
+    fn call_builtin(&mut self) -> Result<()> {
+        let function = Rc::clone(&self.frame().closure.function);
+        let builtin = function.builtin.as_ref().expect("a built-in");
+        let FrameKind::Application { span, .. } = self.frame().kind else {
+            unreachable!("built-ins only run in applications")
+        };
+        let value = self.pop();
+        let number = match value {
+            Value::Literal(number) => Some(number),
+            _ => None,
+        };
+        let param = function.param.as_deref().unwrap_or_default();
+        if let Value::List(list) = &value
+            && let Some(result) = builtin.call_list(list)
+        {
+            self.stack.push(result);
+            return Ok(());
+        }
+        let result = match (&value, &builtin.argument) {
+            (Value::String(text), Argument::Text) => builtin.call_text(&mut self.host, text),
+            _ => match builtin.argument.check(number) {
+                Some(argument) => builtin.call(&mut self.host, argument),
+                None => {
+                    return Err(self
+                        .error(format!("λ@{} only takes {}", param, builtin.expects), span)
+                        .with_found(value));
+                }
+            },
+        };
+        let result = match result {
+            Ok(EvaluationValue::Literal(literal)) => Value::Literal(literal),
+            Ok(EvaluationValue::String(text)) => Value::String(text),
+            Ok(EvaluationValue::Unit) => Value::Unit,
+            Ok(EvaluationValue::HALT) => Value::HALT,
+            Ok(found) => {
+                return Err(self
+                    .error(
+                        format!("λ@{} returned a value the VM can't hold", param),
+                        span,
+                    )
+                    .with_found(found));
+            }
+            Err(e) => return Err(self.error(e.to_string(), span)),
+        };
+        self.stack.push(result);
+        Ok(())
+    }
+
+    // Sets up the next iteration of the abstraction with the argument 𝑓
+    // produced, true when it should start over. Once that argument is 0 the
+    // frame returns HALT instead, and without a pending 𝑓 there's nothing to
+    // do.
+    fn next_iteration(&mut self) -> bool {
+        let frame = self.frames.last_mut().expect("a running frame");
+        let FrameKind::Application {
+            next_arg,
+            recursion_depth,
+            ..
+        } = &mut frame.kind
+        else {
+            return false;
+        };
+        let Some(next) = next_arg.take() else {
+            return false;
+        };
+        self.stack.truncate(frame.base);
+        if let Value::Literal(0.) = next {
+            self.stack.push(Value::HALT);
+            return false;
+        }
+        *recursion_depth += 1;
+        frame.param = next;
+        true
+    }
+}
+
+impl Element for Value {
+    fn number(number: f64) -> Self {
+        Value::Literal(number)
+    }
+
+    fn list(list: List<Self>) -> Self {
+        Value::List(list)
+    }
+
+    fn comparable(&self) -> Option<Comparable<'_, Self>> {
+        match self {
+            Value::Literal(number) => Some(Comparable::Number(*number)),
+            Value::String(text) => Some(Comparable::Text(text)),
+            Value::List(list) => Some(Comparable::List(list)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::MemoryHost;
+    use crate::{Interpreter, ast::Parser, lexer::Lexer};
+
+    // Output of `source` on both backends.
+    fn outputs(source: &str, input: &str) -> (String, String) {
+        let tokens = Lexer::new(source).get_tokens().unwrap();
+        let mut program = Parser::parse_program(tokens).unwrap();
+        let mut interpreter =
+            Interpreter::with_host(MemoryHost::new(input), InterpreterConfig::default())
+                .with_builtins(BuiltinRegistry::standard());
+        let mut vm = Vm::with_host(MemoryHost::new(input), InterpreterConfig::default())
+            .with_builtins(BuiltinRegistry::standard());
+        let interpreted = interpreter.evaluate_program(&mut program).map(|_| ());
+        let compiled = vm.evaluate_program(&mut program).map(|_| ());
+        assert_eq!(interpreted.is_ok(), compiled.is_ok(), "{}", source);
+        (
+            interpreter.host().output_string(),
+            vm.host_mut().output_string(),
+        )
+    }
+
+    #[test]
+    fn matches_the_interpreter() {
+        // What each construct does is tested next to the interpreter, here
+        // the VM only has to agree with it, errors included.
+        let programs = [
+            (include_str!("../code.lamda"), "4"),
+            ("(λ@print. 𝑓(print-1)) 10\n(λ@ascii. ascii) 10", ""),
+            (
+                "a = λx.λy. x * y\nvalue = ((a) 2) 10\n(λ@print. value) 0",
+                "",
+            ),
+            (
+                "mul = λx y. x * y\ndouble = (mul) 2\n(λ@print. (mul) 6 7) 0 (λ@print. (double) 21) 0",
+                "",
+            ),
+            ("f = λx. g + x\ng = 40\n(λ@print. (f) 2) 0", ""),
+            ("(λ@print. print) (λv. v + 10) 10", ""),
+            ("(λ@print. (λx. x) λn.𝑓(0)) 0", ""),
+            ("n = (λ@input. input) 1\n(λ@print. 𝑓(print - n)) 12", "4"),
+            ("(λ@print. 5) 300\n(λ@ascii. 300) 0", ""),
+            ("(λ@print. x) 0", ""),
+            (
+                "(λ@print. 0 - 7 % 3) 0 (λ@print. (0 - 7) div 2) 0 (λ@print. 2 ** 3 ** 2) 0\n\
+                 (λ@print. (0 - 16) >> 2) 0 (λ@print. 1 << 64) 0 (λ@print. 6.9 | 5 ^ 3 & 1) 0",
+                "",
+            ),
+            (
+                "x = 3\n(λ@print. x <= 3 && x != 2 || 0) 0 (λ@print. !(x - 3) == 1) 0\n\
+                 (λ@print. !λv. v) 0",
+                "",
+            ),
+            (
+                "(λ@print. if 3 > 2 then 1 else (λ@ascii. 65) 0) 0\n\
+                 (λ@print. if print > 0 then 𝑓(print - 1) else 0) 3\n\
+                 (λ@print. if (λn. 𝑓(0)) 1 then 1 else 2) 0 if λv. v then 1 else 2",
+                "",
+            ),
+            ("(λ@write. \"a\\t\\u{3bb}\\n\") 0\n(λ@print. \"b\") 0", ""),
+            (
+                "(λ@char. (λ@input. input) 2) 0 (λ@print. (λ@input. input) 2) 0\n\
+                 (λ@ascii. 255) 0 (λ@char. 55296) 0",
+                "é😀",
+            ),
+            (
+                "xs = [1, \"two\", [3]]\nys = [0, ..xs]\n\
+                 (λ@print. (λ@length. ys) 0) 0 (λ@print. (λ@head. (λ@tail. ys) 0) 0) 0\n\
+                 (λ@print. ys == [0, 1, \"two\", [3]]) 0 (λ@head. []) 0",
+                "",
+            ),
+            (
+                "x = 1\n(λ@print. let x = x + 1 in let f = λy. x * y in (f) 10) 0\n\
+                 (λ@print. let print = print - 1 in if print > 0 then 𝑓(print) else 0) 3",
+                "",
+            ),
+            (
+                "length = λhead. head + 1\n(λ@print. (length) 2) 0 (λ@print. (λtail. tail) 4) 0",
+                "",
+            ),
+            // 𝑓 right in the body, and where it's a value handed on.
+            (
+                "(λ@print. let m = print - 1 in 𝑓(m)) 3\n(λn. 𝑓(\"a\")) 1",
+                "",
+            ),
+            (
+                "(λ@print. (λx. x) 𝑓(print - 1)) 3\n(λn. (λx. x) 𝑓(\"a\")) 1",
+                "",
+            ),
+        ];
+        for (source, input) in programs {
+            let (interpreted, compiled) = outputs(source, input);
+            assert_eq!(interpreted, compiled, "{}", source);
+        }
+    }
+
+    #[test]
+    fn deep_recursion_runs_in_constant_stack() {
+        let handle = std::thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(|| {
+                let tokens = Lexer::new("(λn. 𝑓(n-1)) 1000000").get_tokens().unwrap();
+                let mut program = Parser::parse_program(tokens).unwrap();
+                let mut vm = Vm::with_host(MemoryHost::new(""), InterpreterConfig::default());
+                matches!(vm.evaluate_program(&mut program).unwrap()[0], Value::HALT)
+            })
+            .unwrap();
+        assert!(handle.join().unwrap());
+    }
+}